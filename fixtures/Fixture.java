@@ -0,0 +1,78 @@
+package fixture;
+
+import java.io.IOException;
+import java.util.function.IntSupplier;
+
+/**
+ * A small class exercising the parts of the class file format that the class artifacts read.
+ * Compiled with {@code javac --release 17 -d fixtures/classes fixtures/Fixture.java}.
+ */
+public class Fixture implements Comparable<Fixture> {
+    public static final int ANSWER = 42;
+    protected long total;
+    private String name = "fixture";
+
+    public Fixture() {
+    }
+
+    @Override
+    public int compareTo(Fixture other) {
+        return Long.compare(total, other.total);
+    }
+
+    public static String describe(int value) {
+        switch (value) {
+            case 1:
+                return "one";
+            case 2:
+                return "two";
+            case 3:
+                return "three";
+            default:
+                return "many";
+        }
+    }
+
+    public static int sparse(int value) {
+        switch (value) {
+            case 1:
+                return 10;
+            case 1000:
+                return 20;
+            case 100000:
+                return 30;
+            default:
+                return 0;
+        }
+    }
+
+    protected String greet(String who) throws IOException {
+        return "Hello " + who + " from " + name;
+    }
+
+    public IntSupplier supplier() {
+        return () -> ANSWER;
+    }
+
+    public static class Public {
+        protected void run() {
+        }
+    }
+
+    protected static class Protected {
+        public void run() {
+        }
+    }
+
+    private static class Private {
+        public void run() {
+        }
+
+        public static class Hidden {
+        }
+    }
+
+    public interface Callback {
+        void call();
+    }
+}
@@ -0,0 +1,492 @@
+use crate::class_artifacts::{api_signatures_path, parse_api_signatures};
+use crate::colorize::InfoColors;
+use crate::decompiler::DecompileArtifact;
+use crate::html::escape_html;
+use crate::rename_detection::{class_name, detect_renames, ClassRename};
+use crate::repository::{FileChangeKind, MojRepository};
+use crate::{open_repository, Config, MojError, MojResult};
+use error_stack::ResultExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+/// Something that exists in both versions with a different value: a method, keyed as in the API
+/// signatures, or a library, keyed by `group:name`. A method whose parameters changed is keyed
+/// without them, and they start its `from` and `to` instead.
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub key: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PackageChurn {
+    pub package: String,
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ChangeReport {
+    pub from: String,
+    pub to: String,
+    pub classes_added: Vec<String>,
+    pub classes_removed: Vec<String>,
+    pub classes_renamed: Vec<ClassRename>,
+    pub methods_added: Vec<String>,
+    pub methods_removed: Vec<String>,
+    pub methods_changed: Vec<Change>,
+    pub libraries_added: Vec<String>,
+    pub libraries_removed: Vec<String>,
+    pub libraries_changed: Vec<Change>,
+    /// Sorted by the number of changed lines, most changed first.
+    pub packages: Vec<PackageChurn>,
+}
+
+fn package_name(class_name: &str) -> String {
+    match class_name.rsplit_once('.') {
+        Some((package, _)) => package.to_string(),
+        None => "(default package)".to_string(),
+    }
+}
+
+/// Parse the lines of the API signatures of the methods, keyed by the part before the first space.
+fn method_signatures(contents: Option<Vec<u8>>) -> BTreeMap<String, String> {
+    let contents = contents.unwrap_or_default();
+    parse_api_signatures(&String::from_utf8_lossy(&contents))
+        .filter(|(key, _)| key.contains('('))
+        .map(|(key, declaration)| (key.to_string(), declaration.to_string()))
+        .collect()
+}
+
+/// Parse the libraries list, as `group:name` to version.
+fn libraries(contents: Option<Vec<u8>>) -> BTreeMap<String, String> {
+    let contents = contents.unwrap_or_default();
+    String::from_utf8_lossy(&contents)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match line.match_indices(':').nth(1) {
+            Some((i, _)) => (line[..i].to_string(), line[i + 1..].to_string()),
+            None => (line.to_string(), String::new()),
+        })
+        .collect()
+}
+
+impl ChangeReport {
+    pub fn generate(repo: &MojRepository, from: &str, to: &str) -> MojResult<Self> {
+        let mut report = ChangeReport {
+            from: from.to_string(),
+            to: to.to_string(),
+            ..Default::default()
+        };
+
+        let renames = detect_renames(repo, repo.version_tree(from)?, repo.version_tree(to)?)?;
+        let renamed_from: HashSet<_> = renames.iter().map(|r| r.from.clone()).collect();
+        let renamed_to: HashSet<_> = renames.iter().map(|r| r.to.clone()).collect();
+
+        let sources = DecompileArtifact::DecompiledClasses.path_in_repository();
+        let mut packages = BTreeMap::<String, PackageChurn>::new();
+        for change in repo.diff_versions(from, to, sources)? {
+            let old_class = change.old_path.as_deref().and_then(class_name);
+            let new_class = change.new_path.as_deref().and_then(class_name);
+            let Some(class) = new_class.clone().or(old_class.clone()) else {
+                continue;
+            };
+            // Git's rename detection only sees near-identical files, so the detected renames
+            // decide which classes were moved
+            if change.kind != FileChangeKind::Modified {
+                if let Some(old_class) = old_class.filter(|c| !renamed_from.contains(c)) {
+                    report.classes_removed.push(old_class);
+                }
+                if let Some(new_class) = new_class.filter(|c| !renamed_to.contains(c)) {
+                    report.classes_added.push(new_class);
+                }
+            }
+            let package = packages.entry(package_name(&class)).or_default();
+            package.files += 1;
+            package.insertions += change.insertions;
+            package.deletions += change.deletions;
+        }
+        report.classes_added.sort();
+        report.classes_removed.sort();
+        report.classes_renamed = renames;
+        report.packages = packages
+            .into_iter()
+            .map(|(name, churn)| PackageChurn {
+                package: name,
+                ..churn
+            })
+            .collect();
+        report
+            .packages
+            .sort_by_key(|p| std::cmp::Reverse(p.insertions + p.deletions));
+
+        let api_path = api_signatures_path();
+        let old_methods = method_signatures(repo.read_version_file(from, &api_path)?);
+        let new_methods = method_signatures(repo.read_version_file(to, &api_path)?);
+        let mut methods = compare(&old_methods, &new_methods);
+        pair_overloads(&mut methods, &old_methods, &new_methods);
+        report.methods_added = methods.added;
+        report.methods_removed = methods.removed;
+        report.methods_changed = methods.changed;
+
+        let libraries_path = format!(
+            "{}/libraries.txt",
+            DecompileArtifact::LibrariesTxt.path_in_repository()
+        );
+        let old_libraries = libraries(repo.read_version_file(from, &libraries_path)?);
+        let new_libraries = libraries(repo.read_version_file(to, &libraries_path)?);
+        let libraries = compare(&old_libraries, &new_libraries);
+        report.libraries_added = libraries
+            .added
+            .into_iter()
+            .map(|library| format!("{}:{}", library, new_libraries[&library]))
+            .collect();
+        report.libraries_removed = libraries
+            .removed
+            .into_iter()
+            .map(|library| format!("{}:{}", library, old_libraries[&library]))
+            .collect();
+        report.libraries_changed = libraries.changed;
+
+        Ok(report)
+    }
+
+    pub fn render(&self, format: ReportFormat) -> MojResult<String> {
+        match format {
+            ReportFormat::Markdown => Ok(self.render_markdown()),
+            ReportFormat::Json => serde_json::to_string_pretty(self)
+                .map(|json| json + "\n")
+                .change_context(MojError::Report)
+                .attach("Failed to serialize report"),
+            ReportFormat::Html => Ok(self.render_html()),
+        }
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "# Changes from {} to {}", self.from, self.to).unwrap();
+        let lists = [
+            ("Added classes", &self.classes_added),
+            ("Removed classes", &self.classes_removed),
+        ];
+        for (title, items) in lists {
+            markdown_list(&mut out, title, items.iter().map(|i| format!("`{}`", i)));
+        }
+        markdown_list(
+            &mut out,
+            "Renamed classes",
+            self.classes_renamed
+                .iter()
+                .map(|r| format!("`{}` → `{}` ({:.0}%)", r.from, r.to, r.similarity * 100.0)),
+        );
+        let lists = [
+            ("Added methods", &self.methods_added),
+            ("Removed methods", &self.methods_removed),
+        ];
+        for (title, items) in lists {
+            markdown_list(&mut out, title, items.iter().map(|i| format!("`{}`", i)));
+        }
+        markdown_list(
+            &mut out,
+            "Changed method signatures",
+            self.methods_changed
+                .iter()
+                .map(|c| format!("`{}`: `{}` → `{}`", c.key, c.from, c.to)),
+        );
+        let lists = [
+            ("Added libraries", &self.libraries_added),
+            ("Removed libraries", &self.libraries_removed),
+        ];
+        for (title, items) in lists {
+            markdown_list(&mut out, title, items.iter().map(|i| format!("`{}`", i)));
+        }
+        markdown_list(
+            &mut out,
+            "Changed libraries",
+            self.libraries_changed
+                .iter()
+                .map(|c| format!("`{}`: {} → {}", c.key, c.from, c.to)),
+        );
+
+        writeln!(out, "\n## Line churn per package\n").unwrap();
+        if self.packages.is_empty() {
+            writeln!(out, "None.").unwrap();
+        } else {
+            writeln!(out, "| Package | Files | Insertions | Deletions |").unwrap();
+            writeln!(out, "| --- | ---: | ---: | ---: |").unwrap();
+            for p in &self.packages {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {} | {} |",
+                    p.package, p.files, p.insertions, p.deletions
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+
+    fn render_html(&self) -> String {
+        let mut out = String::new();
+        let title = format!("Changes from {} to {}", self.from, self.to);
+        writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+        )
+        .unwrap();
+        writeln!(out, "<title>{}</title>", escape_html(&title)).unwrap();
+        writeln!(
+            out,
+            "<style>body {{ font-family: sans-serif; }} code {{ font-size: 0.9em; }} \
+             td {{ padding: 0 0.5em; }} td.n {{ text-align: right; }}</style>"
+        )
+        .unwrap();
+        writeln!(out, "</head>\n<body>\n<h1>{}</h1>", escape_html(&title)).unwrap();
+        let code = |s: &str| format!("<code>{}</code>", escape_html(s));
+        let lists = [
+            ("Added classes", &self.classes_added),
+            ("Removed classes", &self.classes_removed),
+        ];
+        for (title, items) in lists {
+            html_list(&mut out, title, items.iter().map(|i| code(i)));
+        }
+        html_list(
+            &mut out,
+            "Renamed classes",
+            self.classes_renamed.iter().map(|r| {
+                format!(
+                    "{} → {} ({:.0}%)",
+                    code(&r.from),
+                    code(&r.to),
+                    r.similarity * 100.0
+                )
+            }),
+        );
+        let lists = [
+            ("Added methods", &self.methods_added),
+            ("Removed methods", &self.methods_removed),
+        ];
+        for (title, items) in lists {
+            html_list(&mut out, title, items.iter().map(|i| code(i)));
+        }
+        html_list(
+            &mut out,
+            "Changed method signatures",
+            self.methods_changed
+                .iter()
+                .map(|c| format!("{}: {} → {}", code(&c.key), code(&c.from), code(&c.to))),
+        );
+        let lists = [
+            ("Added libraries", &self.libraries_added),
+            ("Removed libraries", &self.libraries_removed),
+        ];
+        for (title, items) in lists {
+            html_list(&mut out, title, items.iter().map(|i| code(i)));
+        }
+        html_list(
+            &mut out,
+            "Changed libraries",
+            self.libraries_changed.iter().map(|c| {
+                format!(
+                    "{}: {} → {}",
+                    code(&c.key),
+                    escape_html(&c.from),
+                    escape_html(&c.to)
+                )
+            }),
+        );
+
+        writeln!(out, "<h2>Line churn per package</h2>").unwrap();
+        if self.packages.is_empty() {
+            writeln!(out, "<p>None.</p>").unwrap();
+        } else {
+            writeln!(
+                out,
+                "<table>\n<tr><th>Package</th><th>Files</th><th>Insertions</th><th>Deletions</th></tr>"
+            )
+            .unwrap();
+            for p in &self.packages {
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>",
+                    code(&p.package),
+                    p.files,
+                    p.insertions,
+                    p.deletions
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+        writeln!(out, "</body>\n</html>").unwrap();
+        out
+    }
+}
+
+struct Comparison {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<Change>,
+}
+
+/// Compare two keyed maps, finding the added and removed keys and the keys whose values changed.
+fn compare(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Comparison {
+    Comparison {
+        added: new
+            .keys()
+            .filter(|key| !old.contains_key(*key))
+            .cloned()
+            .collect(),
+        removed: old
+            .keys()
+            .filter(|key| !new.contains_key(*key))
+            .cloned()
+            .collect(),
+        changed: old
+            .iter()
+            .filter_map(|(key, from)| {
+                let to = new.get(key).filter(|to| *to != from)?;
+                Some(Change {
+                    key: key.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Treat a method that lost one overload and gained another as changed, as that is most likely a
+/// change of its parameters. Methods that lost or gained several overloads are left alone, as
+/// there is no telling which became which.
+fn pair_overloads(
+    methods: &mut Comparison,
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) {
+    fn by_name(keys: &[String]) -> BTreeMap<&str, Vec<&String>> {
+        let mut by_name = BTreeMap::<&str, Vec<&String>>::new();
+        for key in keys {
+            let name = key.split_once('(').map_or(key.as_str(), |(name, _)| name);
+            by_name.entry(name).or_default().push(key);
+        }
+        by_name
+    }
+    let removed = by_name(&methods.removed);
+    let added = by_name(&methods.added);
+    let mut paired_from = HashSet::new();
+    let mut paired_to = HashSet::new();
+    for (name, removed_keys) in removed {
+        let (&[from_key], Some(&[to_key])) =
+            (removed_keys.as_slice(), added.get(name).map(Vec::as_slice))
+        else {
+            continue;
+        };
+        let parameters = |key: &str| key[name.len()..].to_string();
+        methods.changed.push(Change {
+            key: name.to_string(),
+            from: format!("{} {}", parameters(from_key), old[from_key]),
+            to: format!("{} {}", parameters(to_key), new[to_key]),
+        });
+        paired_from.insert(from_key.clone());
+        paired_to.insert(to_key.clone());
+    }
+    methods.removed.retain(|key| !paired_from.contains(key));
+    methods.added.retain(|key| !paired_to.contains(key));
+    methods.changed.sort_by(|a, b| a.key.cmp(&b.key));
+}
+
+fn markdown_list(out: &mut String, title: &str, items: impl ExactSizeIterator<Item = String>) {
+    writeln!(out, "\n## {} ({})\n", title, items.len()).unwrap();
+    if items.len() == 0 {
+        writeln!(out, "None.").unwrap();
+    }
+    for item in items {
+        writeln!(out, "- {}", item).unwrap();
+    }
+}
+
+fn html_list(out: &mut String, title: &str, items: impl ExactSizeIterator<Item = String>) {
+    writeln!(out, "<h2>{} ({})</h2>", escape_html(title), items.len()).unwrap();
+    if items.len() == 0 {
+        writeln!(out, "<p>None.</p>").unwrap();
+        return;
+    }
+    writeln!(out, "<ul>").unwrap();
+    for item in items {
+        writeln!(out, "<li>{}</li>", item).unwrap();
+    }
+    writeln!(out, "</ul>").unwrap();
+}
+
+/// Summarize the changes between two versions, and print the report or write it to a file.
+pub fn report(
+    config: &Config,
+    from: &str,
+    to: &str,
+    format: ReportFormat,
+    output: Option<PathBuf>,
+) -> MojResult<()> {
+    let repo = open_repository(config)?;
+    log::info!(
+        "Comparing {} to {}...",
+        from.as_important_value(),
+        to.as_important_value()
+    );
+    let rendered = ChangeReport::generate(&repo, from, to)?.render(format)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)
+                .change_context(MojError::Report)
+                .attach_with(|| format!("Path: {:?}", path))?;
+            log::info!("Wrote report to {}", path.display().as_important_value());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signatures(lines: &[&str]) -> BTreeMap<String, String> {
+        method_signatures(Some(lines.join("\n").into_bytes()))
+    }
+
+    #[test]
+    fn pairs_single_overloads_as_changed() {
+        let old = signatures(&[
+            "a.B#run(int) public void",
+            "a.B#get(int) public int",
+            "a.B#get(long) public int",
+            "a.B#gone() public void",
+        ]);
+        let new = signatures(&[
+            "a.B#run(int,long) public void",
+            "a.B#get(int) public int",
+            "a.B#get(float) public int",
+            "a.B#get(double) public int",
+        ]);
+        let mut methods = compare(&old, &new);
+        pair_overloads(&mut methods, &old, &new);
+        assert_eq!(methods.removed, ["a.B#get(long)", "a.B#gone()"]);
+        assert_eq!(methods.added, ["a.B#get(double)", "a.B#get(float)"]);
+        assert_eq!(methods.changed.len(), 1);
+        let change = &methods.changed[0];
+        assert_eq!(change.key, "a.B#run");
+        assert_eq!(change.from, "(int) public void");
+        assert_eq!(change.to, "(int,long) public void");
+    }
+}
@@ -0,0 +1,406 @@
+use crate::classfile::{
+    access, binary_name, element_class_name, field_modifiers, method_modifiers, render_field_type,
+    render_method_type, ClassFile, Constant, Member,
+};
+use crate::colorize::InfoColors;
+use crate::decompiler::DecompileArtifact;
+use crate::disassembler::{render_class_declaration, render_class_listing};
+use crate::{MojError, MojResult};
+use error_stack::ResultExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Read every class file out of the jars in the given directory, keyed by internal name.
+pub fn read_classes(jars_dir: &Path) -> MojResult<BTreeMap<String, ClassFile>> {
+    let mut classes = BTreeMap::new();
+    let mut class_jars: HashMap<String, PathBuf> = HashMap::new();
+    for entry in walkdir::WalkDir::new(jars_dir).sort_by_file_name() {
+        let entry = entry.change_context(MojError::Decompilation)?;
+        if !entry.file_type().is_file() || entry.path().extension() != Some("jar".as_ref()) {
+            continue;
+        }
+        let jar_file = std::fs::File::open(entry.path())
+            .change_context(MojError::Decompilation)
+            .attach_with(|| format!("Path: {:?}", entry.path()))?;
+        let mut jar = zip::ZipArchive::new(jar_file)
+            .change_context(MojError::Decompilation)
+            .attach("Failed to open jar")
+            .attach_with(|| format!("Path: {:?}", entry.path()))?;
+        for i in 0..jar.len() {
+            let mut file = jar
+                .by_index(i)
+                .change_context(MojError::Decompilation)
+                .attach_with(|| format!("Path: {:?}", entry.path()))?;
+            // Multi-release variants would shadow the main class, so skip everything in META-INF
+            if !file.is_file()
+                || !file.name().ends_with(".class")
+                || file.name().starts_with("META-INF/")
+            {
+                continue;
+            }
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)
+                .change_context(MojError::Decompilation)
+                .attach_with(|| format!("Entry: {}", file.name()))?;
+            let class = ClassFile::parse(&data)
+                .change_context(MojError::Decompilation)
+                .attach("Failed to parse class file")
+                .attach_with(|| format!("Entry: {}", file.name()))
+                .attach_with(|| format!("Jar: {:?}", entry.path()))?;
+            // Jars are read in order of their names, so the same copy wins every time
+            if let Some(first_jar) = class_jars.get(&class.this_class) {
+                log::warn!(
+                    "Class {} is in both {} and {}, ignoring the second",
+                    binary_name(&class.this_class).as_important_value(),
+                    first_jar.display(),
+                    entry.path().display()
+                );
+                continue;
+            }
+            class_jars.insert(class.this_class.clone(), entry.path().to_path_buf());
+            classes.insert(class.this_class.clone(), class);
+        }
+    }
+    Ok(classes)
+}
+
+/// Clear out the given directory so an artifact can be written to it from scratch.
+fn recreate_dir(dir: &Path) -> MojResult<()> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .change_context(MojError::Decompilation)
+            .attach_with(|| format!("Path: {:?}", dir))?;
+    }
+    std::fs::create_dir_all(dir)
+        .change_context(MojError::Decompilation)
+        .attach_with(|| format!("Path: {:?}", dir))
+}
+
+/// Write a javap-style listing for each class, at the class's path with a `.txt` extension.
+pub fn write_class_listings(
+    classes: &BTreeMap<String, ClassFile>,
+    output_dir: &Path,
+    include_bytecode: bool,
+) -> MojResult<()> {
+    recreate_dir(output_dir)?;
+    for (name, class) in classes {
+        let listing = render_class_listing(class, include_bytecode)
+            .change_context(MojError::Decompilation)
+            .attach("Failed to render class listing")
+            .attach_with(|| format!("Class: {}", name))?;
+        let path = output_dir.join(format!("{}.txt", name));
+        std::fs::create_dir_all(path.parent().unwrap())
+            .change_context(MojError::Decompilation)
+            .attach_with(|| format!("Path: {:?}", path))?;
+        std::fs::write(&path, listing)
+            .change_context(MojError::Decompilation)
+            .attach("Failed to write class listing")
+            .attach_with(|| format!("Path: {:?}", path))?;
+    }
+    Ok(())
+}
+
+fn is_api_visible(access_flags: u16) -> bool {
+    access_flags & (access::PUBLIC | access::PROTECTED) != 0
+        && access_flags & access::SYNTHETIC == 0
+}
+
+/// Whether a class can be referred to from outside its package. A nested class is only as
+/// visible as the classes enclosing it.
+fn is_class_api_visible(class: &ClassFile, classes: &BTreeMap<String, ClassFile>) -> bool {
+    if !is_api_visible(class.declared_access_flags()) {
+        return false;
+    }
+    match class.nesting() {
+        None => true,
+        Some(nesting) => match &nesting.outer_class {
+            // Local and anonymous classes can't be named outside their method
+            None => false,
+            // The enclosing class might not be in the jar, which leaves only the class's own access
+            Some(outer_class) => classes
+                .get(outer_class)
+                .is_none_or(|outer| is_class_api_visible(outer, classes)),
+        },
+    }
+}
+
+/// Render one line per public or protected class, field and method. Each line starts with a key
+/// identifying the symbol, e.g. `a.b.C#foo(int,long)`, followed by a space and its declaration.
+pub fn api_signatures(classes: &BTreeMap<String, ClassFile>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for class in classes.values() {
+        if !is_class_api_visible(class, classes) {
+            continue;
+        }
+        let class_name = binary_name(&class.this_class);
+        let mut line = format!("{} {}", class_name, render_class_declaration(class));
+        push_generic_signature(&mut line, class.signature.as_deref());
+        lines.push(line);
+
+        for field in class
+            .fields
+            .iter()
+            .filter(|f| is_api_visible(f.access_flags))
+        {
+            let mut declaration = field_modifiers(field.access_flags);
+            let field_type = render_field_type(&field.descriptor);
+            declaration.push(&field_type);
+            let mut line = format!("{}#{} {}", class_name, field.name, declaration.join(" "));
+            push_generic_signature(&mut line, field.signature.as_deref());
+            lines.push(line);
+        }
+
+        for method in class
+            .methods
+            .iter()
+            .filter(|m| is_api_visible(m.access_flags))
+        {
+            lines.push(method_api_signature(&class_name, method));
+        }
+    }
+    lines.sort();
+    lines
+}
+
+/// The key used for a method in the API signatures and the reference index, e.g. `a.b.C#foo(int)`.
+fn method_key(class_name: &str, name: &str, descriptor: &str) -> String {
+    let params = render_method_type(descriptor)
+        .map(|(params, _)| params)
+        .unwrap_or_else(|| vec![descriptor.to_string()]);
+    format!("{}#{}({})", class_name, name, params.join(","))
+}
+
+fn method_api_signature(class_name: &str, method: &Member) -> String {
+    let return_type = render_method_type(&method.descriptor)
+        .map(|(_, return_type)| return_type)
+        .unwrap_or_default();
+    let mut declaration = method_modifiers(method.access_flags);
+    if method.name != "<init>" {
+        declaration.push(&return_type);
+    }
+    let mut line = format!(
+        "{} {}",
+        method_key(class_name, &method.name, &method.descriptor),
+        declaration.join(" ")
+    );
+    if !method.exceptions.is_empty() {
+        let exceptions: Vec<_> = method.exceptions.iter().map(|e| binary_name(e)).collect();
+        line.push_str(&format!(" throws {}", exceptions.join(", ")));
+    }
+    push_generic_signature(&mut line, method.signature.as_deref());
+    line
+}
+
+fn push_generic_signature(line: &mut String, signature: Option<&str>) {
+    if let Some(signature) = signature {
+        line.push_str(" signature ");
+        line.push_str(signature);
+    }
+}
+
+/// The path of the API signature dump in the repository.
+pub fn api_signatures_path() -> String {
+    format!(
+        "{}/api.txt",
+        DecompileArtifact::ApiSignatures.path_in_repository()
+    )
+}
+
+/// Parse an API signature dump into `(key, declaration)` pairs.
+pub fn parse_api_signatures(contents: &str) -> impl Iterator<Item = (&str, &str)> {
+    contents.lines().filter_map(|line| line.split_once(' '))
+}
+
+/// Write the sorted API signature dump of the classes to a single file.
+pub fn write_api_signatures(
+    classes: &BTreeMap<String, ClassFile>,
+    output_file: &Path,
+) -> MojResult<()> {
+    let mut contents = api_signatures(classes).join("\n");
+    contents.push('\n');
+    std::fs::write(output_file, contents)
+        .change_context(MojError::Decompilation)
+        .attach("Failed to write API signatures")
+        .attach_with(|| format!("Path: {:?}", output_file))
+}
+
+#[derive(Debug, Default, Serialize)]
+struct HierarchyEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    superclass: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    interfaces: Vec<String>,
+    /// Classes directly extending this class.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    subclasses: BTreeSet<String>,
+    /// Classes and interfaces directly implementing or extending this interface.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    implementors: BTreeSet<String>,
+}
+
+/// For each kind of symbol, the classes referencing it.
+#[derive(Debug, Default, Serialize)]
+struct ReferenceIndex {
+    classes: BTreeMap<String, BTreeSet<String>>,
+    fields: BTreeMap<String, BTreeSet<String>>,
+    methods: BTreeMap<String, BTreeSet<String>>,
+}
+
+fn class_hierarchy(classes: &BTreeMap<String, ClassFile>) -> BTreeMap<String, HierarchyEntry> {
+    let mut hierarchy = BTreeMap::<String, HierarchyEntry>::new();
+    for class in classes.values() {
+        let name = binary_name(&class.this_class);
+        let superclass = class
+            .super_class
+            .as_deref()
+            .filter(|_| !class.is_interface())
+            .map(binary_name);
+        let interfaces: Vec<_> = class.interfaces.iter().map(|i| binary_name(i)).collect();
+        if let Some(superclass) = &superclass {
+            hierarchy
+                .entry(superclass.clone())
+                .or_default()
+                .subclasses
+                .insert(name.clone());
+        }
+        for interface in &interfaces {
+            hierarchy
+                .entry(interface.clone())
+                .or_default()
+                .implementors
+                .insert(name.clone());
+        }
+        let entry = hierarchy.entry(name).or_default();
+        entry.superclass = superclass;
+        entry.interfaces = interfaces;
+    }
+    hierarchy
+}
+
+/// Index the references between classes. Only references to symbols owned by the given classes
+/// are recorded, as references into the JDK and libraries would dwarf the rest of the index.
+fn reference_index(classes: &BTreeMap<String, ClassFile>) -> ReferenceIndex {
+    let mut index = ReferenceIndex::default();
+    for class in classes.values() {
+        let referrer = binary_name(&class.this_class);
+        let pool = &class.constant_pool;
+        for (i, constant) in pool.entries() {
+            match constant {
+                Constant::Class(_) => {
+                    let Some(target) = pool.class_name(i).ok().and_then(element_class_name) else {
+                        continue;
+                    };
+                    if target != class.this_class && classes.contains_key(target) {
+                        index
+                            .classes
+                            .entry(binary_name(target))
+                            .or_default()
+                            .insert(referrer.clone());
+                    }
+                }
+                Constant::FieldRef(..)
+                | Constant::MethodRef(..)
+                | Constant::InterfaceMethodRef(..) => {
+                    let Ok(member) = pool.member_ref(i) else {
+                        continue;
+                    };
+                    if member.owner == class.this_class || !classes.contains_key(&member.owner) {
+                        continue;
+                    }
+                    let owner = binary_name(&member.owner);
+                    let (map, key) = if let Constant::FieldRef(..) = constant {
+                        (&mut index.fields, format!("{}#{}", owner, member.name))
+                    } else {
+                        (
+                            &mut index.methods,
+                            method_key(&owner, &member.name, &member.descriptor),
+                        )
+                    };
+                    map.entry(key).or_default().insert(referrer.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+    index
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> MojResult<()> {
+    let file = std::fs::File::create(path)
+        .change_context(MojError::Decompilation)
+        .attach_with(|| format!("Path: {:?}", path))?;
+    let mut writer = std::io::BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, value)
+        .change_context(MojError::Decompilation)
+        .attach_with(|| format!("Path: {:?}", path))?;
+    writer
+        .write_all(b"\n")
+        .change_context(MojError::Decompilation)
+        .attach_with(|| format!("Path: {:?}", path))
+}
+
+/// Write `hierarchy.json` and `references.json` describing how the classes relate to each other.
+pub fn write_class_index(
+    classes: &BTreeMap<String, ClassFile>,
+    output_dir: &Path,
+) -> MojResult<()> {
+    recreate_dir(output_dir)?;
+    write_json(
+        &output_dir.join("hierarchy.json"),
+        &class_hierarchy(classes),
+    )?;
+    write_json(
+        &output_dir.join("references.json"),
+        &reference_index(classes),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_classes() -> BTreeMap<String, ClassFile> {
+        [
+            &include_bytes!("../fixtures/classes/fixture/Fixture.class")[..],
+            include_bytes!("../fixtures/classes/fixture/Fixture$Public.class"),
+            include_bytes!("../fixtures/classes/fixture/Fixture$Protected.class"),
+            include_bytes!("../fixtures/classes/fixture/Fixture$Private.class"),
+            include_bytes!("../fixtures/classes/fixture/Fixture$Private$Hidden.class"),
+            include_bytes!("../fixtures/classes/fixture/Fixture$Callback.class"),
+        ]
+        .into_iter()
+        .map(|data| {
+            let class = ClassFile::parse(data).unwrap();
+            (class.this_class.clone(), class)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn nested_class_visibility_comes_from_inner_classes() {
+        let classes = fixture_classes();
+        let visible = |name: &str| is_class_api_visible(&classes[name], &classes);
+        assert!(visible("fixture/Fixture"));
+        assert!(visible("fixture/Fixture$Public"));
+        assert!(visible("fixture/Fixture$Protected"));
+        assert!(visible("fixture/Fixture$Callback"));
+        // Compiled as package-private, but declared private
+        assert!(!visible("fixture/Fixture$Private"));
+        // Public, but enclosed by a private class
+        assert!(!visible("fixture/Fixture$Private$Hidden"));
+    }
+
+    #[test]
+    fn api_signatures_use_declared_modifiers() {
+        let lines = api_signatures(&fixture_classes());
+        assert!(lines.contains(
+            &"fixture.Fixture$Protected protected static class fixture.Fixture$Protected extends java.lang.Object"
+                .to_string()
+        ));
+        assert!(lines
+            .iter()
+            .all(|line| !line.starts_with("fixture.Fixture$Private")));
+    }
+}
@@ -0,0 +1,807 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClassFileError {
+    #[error("Unexpected end of class file")]
+    UnexpectedEof,
+    #[error("Bad magic number {0:#010x}")]
+    BadMagic(u32),
+    #[error("Unknown constant pool tag {0}")]
+    UnknownConstantTag(u8),
+    #[error("Invalid constant pool index {0}")]
+    InvalidConstantIndex(u16),
+    #[error("Constant pool entry {0} has the wrong type")]
+    WrongConstantType(u16),
+}
+
+pub type ClassFileResult<T> = Result<T, ClassFileError>;
+
+pub mod access {
+    pub const PUBLIC: u16 = 0x0001;
+    pub const PRIVATE: u16 = 0x0002;
+    pub const PROTECTED: u16 = 0x0004;
+    pub const STATIC: u16 = 0x0008;
+    pub const FINAL: u16 = 0x0010;
+    pub const SYNCHRONIZED: u16 = 0x0020;
+    pub const VOLATILE: u16 = 0x0040;
+    pub const BRIDGE: u16 = 0x0040;
+    pub const TRANSIENT: u16 = 0x0080;
+    pub const VARARGS: u16 = 0x0080;
+    pub const NATIVE: u16 = 0x0100;
+    pub const INTERFACE: u16 = 0x0200;
+    pub const ABSTRACT: u16 = 0x0400;
+    pub const STRICT: u16 = 0x0800;
+    pub const SYNTHETIC: u16 = 0x1000;
+    pub const ANNOTATION: u16 = 0x2000;
+    pub const ENUM: u16 = 0x4000;
+    pub const MODULE: u16 = 0x8000;
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    /// Placeholder for index 0 and the second slot of longs and doubles.
+    Unusable,
+    Utf8(String),
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    Class(u16),
+    String(u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    InterfaceMethodRef(u16, u16),
+    NameAndType(u16, u16),
+    MethodHandle(u8, u16),
+    MethodType(u16),
+    Dynamic(u16, u16),
+    InvokeDynamic(u16, u16),
+    Module(u16),
+    Package(u16),
+}
+
+/// A reference to a field or method of another class, resolved from the constant pool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MemberRef {
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstantPool {
+    entries: Vec<Constant>,
+}
+
+impl ConstantPool {
+    pub fn entries(&self) -> impl Iterator<Item = (u16, &Constant)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i as u16, c))
+            .filter(|(_, c)| !matches!(c, Constant::Unusable))
+    }
+
+    pub fn get(&self, index: u16) -> ClassFileResult<&Constant> {
+        match self.entries.get(index as usize) {
+            Some(Constant::Unusable) | None => Err(ClassFileError::InvalidConstantIndex(index)),
+            Some(c) => Ok(c),
+        }
+    }
+
+    pub fn utf8(&self, index: u16) -> ClassFileResult<&str> {
+        match self.get(index)? {
+            Constant::Utf8(s) => Ok(s),
+            _ => Err(ClassFileError::WrongConstantType(index)),
+        }
+    }
+
+    /// Resolve a `CONSTANT_Class` entry to its internal name.
+    pub fn class_name(&self, index: u16) -> ClassFileResult<&str> {
+        match self.get(index)? {
+            Constant::Class(name) => self.utf8(*name),
+            _ => Err(ClassFileError::WrongConstantType(index)),
+        }
+    }
+
+    pub fn name_and_type(&self, index: u16) -> ClassFileResult<(&str, &str)> {
+        match self.get(index)? {
+            Constant::NameAndType(name, descriptor) => {
+                Ok((self.utf8(*name)?, self.utf8(*descriptor)?))
+            }
+            _ => Err(ClassFileError::WrongConstantType(index)),
+        }
+    }
+
+    /// Resolve a field, method or interface method reference.
+    pub fn member_ref(&self, index: u16) -> ClassFileResult<MemberRef> {
+        match self.get(index)? {
+            Constant::FieldRef(class, nat)
+            | Constant::MethodRef(class, nat)
+            | Constant::InterfaceMethodRef(class, nat) => {
+                let (name, descriptor) = self.name_and_type(*nat)?;
+                Ok(MemberRef {
+                    owner: self.class_name(*class)?.to_string(),
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                })
+            }
+            _ => Err(ClassFileError::WrongConstantType(index)),
+        }
+    }
+
+    /// Render a constant as it would appear as an instruction operand. Constant pool indices are
+    /// never included, as they are not stable between compilations.
+    pub fn display(&self, index: u16) -> ClassFileResult<String> {
+        Ok(match self.get(index)? {
+            Constant::Utf8(s) => format!("{:?}", s),
+            Constant::Integer(i) => format!("int {}", i),
+            Constant::Float(f) => format!("float {:?}", f),
+            Constant::Long(l) => format!("long {}", l),
+            Constant::Double(d) => format!("double {:?}", d),
+            Constant::Class(name) => format!("class {}", self.utf8(*name)?),
+            Constant::String(s) => format!("String {:?}", self.utf8(*s)?),
+            Constant::FieldRef(..) => {
+                let r = self.member_ref(index)?;
+                format!("Field {}.{}:{}", r.owner, r.name, r.descriptor)
+            }
+            Constant::MethodRef(..) => {
+                let r = self.member_ref(index)?;
+                format!("Method {}.{}:{}", r.owner, r.name, r.descriptor)
+            }
+            Constant::InterfaceMethodRef(..) => {
+                let r = self.member_ref(index)?;
+                format!("InterfaceMethod {}.{}:{}", r.owner, r.name, r.descriptor)
+            }
+            Constant::NameAndType(..) => {
+                let (name, descriptor) = self.name_and_type(index)?;
+                format!("NameAndType {}:{}", name, descriptor)
+            }
+            // Only a member reference can be referenced, so a malformed pool can't loop
+            Constant::MethodHandle(kind, reference) => match self.get(*reference)? {
+                Constant::FieldRef(..)
+                | Constant::MethodRef(..)
+                | Constant::InterfaceMethodRef(..) => format!(
+                    "MethodHandle {} {}",
+                    method_handle_kind(*kind),
+                    self.display(*reference)?
+                ),
+                _ => return Err(ClassFileError::WrongConstantType(*reference)),
+            },
+            Constant::MethodType(descriptor) => format!("MethodType {}", self.utf8(*descriptor)?),
+            // The bootstrap method is in an attribute of the class, which the disassembler resolves
+            Constant::Dynamic(_, nat) => {
+                let (name, descriptor) = self.name_and_type(*nat)?;
+                format!("Dynamic {}:{}", name, descriptor)
+            }
+            Constant::InvokeDynamic(_, nat) => {
+                let (name, descriptor) = self.name_and_type(*nat)?;
+                format!("InvokeDynamic {}:{}", name, descriptor)
+            }
+            Constant::Module(name) => format!("Module {}", self.utf8(*name)?),
+            Constant::Package(name) => format!("Package {}", self.utf8(*name)?),
+            Constant::Unusable => unreachable!("get() never returns unusable entries"),
+        })
+    }
+}
+
+fn method_handle_kind(kind: u8) -> &'static str {
+    match kind {
+        1 => "REF_getField",
+        2 => "REF_getStatic",
+        3 => "REF_putField",
+        4 => "REF_putStatic",
+        5 => "REF_invokeVirtual",
+        6 => "REF_invokeStatic",
+        7 => "REF_invokeSpecial",
+        8 => "REF_newInvokeSpecial",
+        9 => "REF_invokeInterface",
+        _ => "REF_unknown",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExceptionHandler {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    /// The caught exception type, or `None` for `finally` handlers.
+    pub catch_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Code {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub bytecode: Vec<u8>,
+    pub exception_table: Vec<ExceptionHandler>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BootstrapMethod {
+    pub method_ref: u16,
+    pub arguments: Vec<u16>,
+}
+
+/// An entry of the `InnerClasses` attribute, describing a nested class.
+#[derive(Debug, Clone)]
+pub struct InnerClass {
+    pub inner_class: String,
+    /// `None` for local and anonymous classes.
+    pub outer_class: Option<String>,
+    /// The flags as declared in the source, which can be `private`, `protected` or `static`
+    /// unlike the flags of the class file itself.
+    pub access_flags: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+    /// The generic signature, if any.
+    pub signature: Option<String>,
+    /// Checked exceptions declared by a method.
+    pub exceptions: Vec<String>,
+    /// The `ConstantValue` attribute of a field, rendered.
+    pub constant_value: Option<String>,
+    pub code: Option<Code>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassFile {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub constant_pool: ConstantPool,
+    pub access_flags: u16,
+    /// The internal name of the class, e.g. `net/minecraft/world/item/Item`.
+    pub this_class: String,
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    pub signature: Option<String>,
+    pub fields: Vec<Member>,
+    pub methods: Vec<Member>,
+    pub bootstrap_methods: Vec<BootstrapMethod>,
+    pub inner_classes: Vec<InnerClass>,
+}
+
+impl ClassFile {
+    pub fn parse(data: &[u8]) -> ClassFileResult<ClassFile> {
+        let mut reader = Reader { data, pos: 0 };
+        let magic = reader.u4()?;
+        if magic != 0xCAFEBABE {
+            return Err(ClassFileError::BadMagic(magic));
+        }
+        let minor_version = reader.u2()?;
+        let major_version = reader.u2()?;
+        let constant_pool = read_constant_pool(&mut reader)?;
+        let access_flags = reader.u2()?;
+        let this_class = constant_pool.class_name(reader.u2()?)?.to_string();
+        let super_class = match reader.u2()? {
+            0 => None,
+            index => Some(constant_pool.class_name(index)?.to_string()),
+        };
+        let interfaces = (0..reader.u2()?)
+            .map(|_| Ok(constant_pool.class_name(reader.u2()?)?.to_string()))
+            .collect::<ClassFileResult<Vec<_>>>()?;
+        let fields = (0..reader.u2()?)
+            .map(|_| read_member(&mut reader, &constant_pool))
+            .collect::<ClassFileResult<Vec<_>>>()?;
+        let methods = (0..reader.u2()?)
+            .map(|_| read_member(&mut reader, &constant_pool))
+            .collect::<ClassFileResult<Vec<_>>>()?;
+
+        let mut signature = None;
+        let mut bootstrap_methods = Vec::new();
+        let mut inner_classes = Vec::new();
+        for _ in 0..reader.u2()? {
+            let (name, mut attribute) = read_attribute(&mut reader, &constant_pool)?;
+            match name {
+                "Signature" => signature = Some(constant_pool.utf8(attribute.u2()?)?.to_string()),
+                "BootstrapMethods" => {
+                    for _ in 0..attribute.u2()? {
+                        let method_ref = attribute.u2()?;
+                        let arguments = (0..attribute.u2()?)
+                            .map(|_| attribute.u2())
+                            .collect::<ClassFileResult<Vec<_>>>()?;
+                        bootstrap_methods.push(BootstrapMethod {
+                            method_ref,
+                            arguments,
+                        });
+                    }
+                }
+                "InnerClasses" => {
+                    for _ in 0..attribute.u2()? {
+                        let inner_class = constant_pool.class_name(attribute.u2()?)?.to_string();
+                        let outer_class = match attribute.u2()? {
+                            0 => None,
+                            index => Some(constant_pool.class_name(index)?.to_string()),
+                        };
+                        // The simple name, which the binary name already contains
+                        attribute.u2()?;
+                        inner_classes.push(InnerClass {
+                            inner_class,
+                            outer_class,
+                            access_flags: attribute.u2()?,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ClassFile {
+            minor_version,
+            major_version,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            signature,
+            fields,
+            methods,
+            bootstrap_methods,
+            inner_classes,
+        })
+    }
+
+    /// The entry of the `InnerClasses` attribute describing this class itself, if it is nested.
+    pub fn nesting(&self) -> Option<&InnerClass> {
+        self.inner_classes
+            .iter()
+            .find(|inner| inner.inner_class == self.this_class)
+    }
+
+    /// The access flags of the class as declared in the source. Nested classes are compiled as
+    /// public or package-private, so their real access comes from the `InnerClasses` attribute.
+    pub fn declared_access_flags(&self) -> u16 {
+        self.nesting()
+            .map_or(self.access_flags, |nesting| nesting.access_flags)
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.access_flags & access::INTERFACE != 0
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> ClassFileResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or(ClassFileError::UnexpectedEof)?;
+        let slice = self
+            .data
+            .get(self.pos..end)
+            .ok_or(ClassFileError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u1(&mut self) -> ClassFileResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u2(&mut self) -> ClassFileResult<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u4(&mut self) -> ClassFileResult<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u8(&mut self) -> ClassFileResult<u64> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+fn read_constant_pool(reader: &mut Reader) -> ClassFileResult<ConstantPool> {
+    let count = reader.u2()?;
+    let mut entries = Vec::with_capacity(count as usize);
+    entries.push(Constant::Unusable);
+    while entries.len() < count as usize {
+        let tag = reader.u1()?;
+        let constant = match tag {
+            1 => {
+                let len = reader.u2()? as usize;
+                Constant::Utf8(decode_modified_utf8(reader.bytes(len)?))
+            }
+            3 => Constant::Integer(reader.u4()? as i32),
+            4 => Constant::Float(f32::from_bits(reader.u4()?)),
+            5 => Constant::Long(reader.u8()? as i64),
+            6 => Constant::Double(f64::from_bits(reader.u8()?)),
+            7 => Constant::Class(reader.u2()?),
+            8 => Constant::String(reader.u2()?),
+            9 => Constant::FieldRef(reader.u2()?, reader.u2()?),
+            10 => Constant::MethodRef(reader.u2()?, reader.u2()?),
+            11 => Constant::InterfaceMethodRef(reader.u2()?, reader.u2()?),
+            12 => Constant::NameAndType(reader.u2()?, reader.u2()?),
+            15 => Constant::MethodHandle(reader.u1()?, reader.u2()?),
+            16 => Constant::MethodType(reader.u2()?),
+            17 => Constant::Dynamic(reader.u2()?, reader.u2()?),
+            18 => Constant::InvokeDynamic(reader.u2()?, reader.u2()?),
+            19 => Constant::Module(reader.u2()?),
+            20 => Constant::Package(reader.u2()?),
+            _ => return Err(ClassFileError::UnknownConstantTag(tag)),
+        };
+        let wide = matches!(constant, Constant::Long(_) | Constant::Double(_));
+        entries.push(constant);
+        if wide {
+            entries.push(Constant::Unusable);
+        }
+    }
+    Ok(ConstantPool { entries })
+}
+
+/// Read an attribute header, returning its name and a reader over its contents.
+fn read_attribute<'a, 'p>(
+    reader: &mut Reader<'a>,
+    constant_pool: &'p ConstantPool,
+) -> ClassFileResult<(&'p str, Reader<'a>)> {
+    let name = constant_pool.utf8(reader.u2()?)?;
+    let len = reader.u4()? as usize;
+    Ok((
+        name,
+        Reader {
+            data: reader.bytes(len)?,
+            pos: 0,
+        },
+    ))
+}
+
+fn read_member(reader: &mut Reader, constant_pool: &ConstantPool) -> ClassFileResult<Member> {
+    let access_flags = reader.u2()?;
+    let name = constant_pool.utf8(reader.u2()?)?.to_string();
+    let descriptor = constant_pool.utf8(reader.u2()?)?.to_string();
+    let mut member = Member {
+        access_flags,
+        name,
+        descriptor,
+        signature: None,
+        exceptions: Vec::new(),
+        constant_value: None,
+        code: None,
+    };
+    for _ in 0..reader.u2()? {
+        let (name, mut attribute) = read_attribute(reader, constant_pool)?;
+        match name {
+            "Signature" => {
+                member.signature = Some(constant_pool.utf8(attribute.u2()?)?.to_string())
+            }
+            "Exceptions" => {
+                for _ in 0..attribute.u2()? {
+                    member
+                        .exceptions
+                        .push(constant_pool.class_name(attribute.u2()?)?.to_string());
+                }
+            }
+            "ConstantValue" => {
+                member.constant_value = Some(constant_pool.display(attribute.u2()?)?)
+            }
+            "Code" => {
+                let max_stack = attribute.u2()?;
+                let max_locals = attribute.u2()?;
+                let len = attribute.u4()? as usize;
+                let bytecode = attribute.bytes(len)?.to_vec();
+                let exception_table = (0..attribute.u2()?)
+                    .map(|_| {
+                        Ok(ExceptionHandler {
+                            start_pc: attribute.u2()?,
+                            end_pc: attribute.u2()?,
+                            handler_pc: attribute.u2()?,
+                            catch_type: match attribute.u2()? {
+                                0 => None,
+                                index => Some(constant_pool.class_name(index)?.to_string()),
+                            },
+                        })
+                    })
+                    .collect::<ClassFileResult<Vec<_>>>()?;
+                member.code = Some(Code {
+                    max_stack,
+                    max_locals,
+                    bytecode,
+                    exception_table,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(member)
+}
+
+/// Decode the JVM's "modified UTF-8", which encodes NUL as two bytes and supplementary characters
+/// as surrogate pairs.
+fn decode_modified_utf8(bytes: &[u8]) -> String {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return s.to_string();
+    }
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i] as u16;
+        let continuation = |offset: usize| bytes.get(i + offset).map_or(0, |&c| (c & 0x3F) as u16);
+        if b & 0x80 == 0 {
+            units.push(b);
+            i += 1;
+        } else if b & 0xE0 == 0xC0 {
+            units.push(((b & 0x1F) << 6) | continuation(1));
+            i += 2;
+        } else {
+            units.push(((b & 0x0F) << 12) | (continuation(1) << 6) | continuation(2));
+            i += 3;
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+fn modifiers(flags: u16, table: &[(u16, &'static str)]) -> Vec<&'static str> {
+    table
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// The source-level modifiers of a class, excluding the `class`/`interface` keyword itself.
+pub fn class_modifiers(flags: u16) -> Vec<&'static str> {
+    let mut table = vec![
+        (access::PUBLIC, "public"),
+        (access::PROTECTED, "protected"),
+        (access::PRIVATE, "private"),
+        (access::STATIC, "static"),
+    ];
+    // Interfaces are always abstract, so it's noise to say so
+    if flags & access::INTERFACE == 0 {
+        table.push((access::ABSTRACT, "abstract"));
+    }
+    // Enums are implicitly final or abstract in the same way
+    if flags & access::ENUM == 0 {
+        table.push((access::FINAL, "final"));
+    }
+    table.push((access::SYNTHETIC, "synthetic"));
+    modifiers(flags, &table)
+}
+
+/// The keyword used to declare a class, e.g. `class`, `interface` or `enum`.
+pub fn class_keyword(flags: u16) -> &'static str {
+    if flags & access::ANNOTATION != 0 {
+        "@interface"
+    } else if flags & access::INTERFACE != 0 {
+        "interface"
+    } else if flags & access::ENUM != 0 {
+        "enum"
+    } else if flags & access::MODULE != 0 {
+        "module"
+    } else {
+        "class"
+    }
+}
+
+pub fn field_modifiers(flags: u16) -> Vec<&'static str> {
+    modifiers(
+        flags,
+        &[
+            (access::PUBLIC, "public"),
+            (access::PRIVATE, "private"),
+            (access::PROTECTED, "protected"),
+            (access::STATIC, "static"),
+            (access::FINAL, "final"),
+            (access::VOLATILE, "volatile"),
+            (access::TRANSIENT, "transient"),
+            (access::SYNTHETIC, "synthetic"),
+            (access::ENUM, "enum"),
+        ],
+    )
+}
+
+pub fn method_modifiers(flags: u16) -> Vec<&'static str> {
+    modifiers(
+        flags,
+        &[
+            (access::PUBLIC, "public"),
+            (access::PRIVATE, "private"),
+            (access::PROTECTED, "protected"),
+            (access::STATIC, "static"),
+            (access::FINAL, "final"),
+            (access::SYNCHRONIZED, "synchronized"),
+            (access::BRIDGE, "bridge"),
+            (access::VARARGS, "varargs"),
+            (access::NATIVE, "native"),
+            (access::ABSTRACT, "abstract"),
+            (access::STRICT, "strictfp"),
+            (access::SYNTHETIC, "synthetic"),
+        ],
+    )
+}
+
+/// Convert an internal name (`a/b/C`) to a binary name (`a.b.C`).
+pub fn binary_name(internal_name: &str) -> String {
+    internal_name.replace('/', ".")
+}
+
+/// Render a field descriptor as a Java type, e.g. `[Ljava/lang/String;` as `java.lang.String[]`.
+/// Returns the rendered type and the unparsed remainder of the descriptor.
+fn parse_field_type(descriptor: &str) -> Option<(String, &str)> {
+    let mut chars = descriptor.char_indices();
+    let (_, first) = chars.next()?;
+    let rest = &descriptor[first.len_utf8()..];
+    let primitive = match first {
+        'B' => "byte",
+        'C' => "char",
+        'D' => "double",
+        'F' => "float",
+        'I' => "int",
+        'J' => "long",
+        'S' => "short",
+        'Z' => "boolean",
+        'V' => "void",
+        'L' => {
+            let end = rest.find(';')?;
+            return Some((binary_name(&rest[..end]), &rest[end + 1..]));
+        }
+        '[' => {
+            let (component, rest) = parse_field_type(rest)?;
+            return Some((format!("{}[]", component), rest));
+        }
+        _ => return None,
+    };
+    Some((primitive.to_string(), rest))
+}
+
+/// Strip any array dimensions from a `CONSTANT_Class` name, returning the element class if it is
+/// not a primitive.
+pub fn element_class_name(class_name: &str) -> Option<&str> {
+    let element = class_name.trim_start_matches('[');
+    if element.len() == class_name.len() {
+        return Some(class_name);
+    }
+    element.strip_prefix('L')?.strip_suffix(';')
+}
+
+/// Render a field descriptor as a Java type. Unparseable descriptors are returned verbatim.
+pub fn render_field_type(descriptor: &str) -> String {
+    match parse_field_type(descriptor) {
+        Some((rendered, "")) => rendered,
+        _ => descriptor.to_string(),
+    }
+}
+
+/// Split a method descriptor into its rendered parameter types and return type.
+pub fn render_method_type(descriptor: &str) -> Option<(Vec<String>, String)> {
+    let mut rest = descriptor.strip_prefix('(')?;
+    let mut params = Vec::new();
+    while !rest.starts_with(')') {
+        let (param, remainder) = parse_field_type(rest)?;
+        params.push(param);
+        rest = remainder;
+    }
+    let (return_type, remainder) = parse_field_type(&rest[1..])?;
+    if !remainder.is_empty() {
+        return None;
+    }
+    Some((params, return_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/classes/fixture/Fixture.class");
+
+    fn member<'a>(members: &'a [Member], name: &str) -> &'a Member {
+        members.iter().find(|m| m.name == name).unwrap()
+    }
+
+    #[test]
+    fn parses_class_structure() {
+        let class = ClassFile::parse(FIXTURE).unwrap();
+        assert_eq!(class.major_version, 61);
+        assert_eq!(class.this_class, "fixture/Fixture");
+        assert_eq!(class.super_class.as_deref(), Some("java/lang/Object"));
+        assert_eq!(class.interfaces, ["java/lang/Comparable"]);
+        assert_eq!(
+            class.signature.as_deref(),
+            Some("Ljava/lang/Object;Ljava/lang/Comparable<Lfixture/Fixture;>;")
+        );
+        assert!(!class.is_interface());
+        // One for string concatenation, one for the lambda
+        assert_eq!(class.bootstrap_methods.len(), 2);
+    }
+
+    #[test]
+    fn parses_members() {
+        let class = ClassFile::parse(FIXTURE).unwrap();
+        let answer = member(&class.fields, "ANSWER");
+        assert_eq!(answer.descriptor, "I");
+        assert_eq!(
+            answer.access_flags,
+            access::PUBLIC | access::STATIC | access::FINAL
+        );
+        assert_eq!(answer.constant_value.as_deref(), Some("int 42"));
+
+        let greet = member(&class.methods, "greet");
+        assert_eq!(greet.access_flags, access::PROTECTED);
+        assert_eq!(greet.exceptions, ["java/io/IOException"]);
+        assert!(greet
+            .code
+            .as_ref()
+            .is_some_and(|code| !code.bytecode.is_empty()));
+    }
+
+    #[test]
+    fn resolves_member_refs() {
+        let class = ClassFile::parse(FIXTURE).unwrap();
+        let refs: Vec<_> = class
+            .constant_pool
+            .entries()
+            .filter(|(_, c)| matches!(c, Constant::MethodRef(..)))
+            .map(|(i, _)| class.constant_pool.member_ref(i).unwrap())
+            .collect();
+        assert!(refs.contains(&MemberRef {
+            owner: "java/lang/Long".to_string(),
+            name: "compare".to_string(),
+            descriptor: "(JJ)I".to_string(),
+        }));
+    }
+
+    #[test]
+    fn rejects_malformed_class_files() {
+        assert!(matches!(
+            ClassFile::parse(&[0xCA, 0xFE, 0xD0, 0x0D]),
+            Err(ClassFileError::BadMagic(0xCAFED00D))
+        ));
+        assert!(matches!(
+            ClassFile::parse(&FIXTURE[..FIXTURE.len() / 2]),
+            Err(ClassFileError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn rejects_self_referencing_constants() {
+        // A class with one constant field, whose value is a method handle referencing itself
+        let mut class = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 61, 0, 7];
+        for name in ["A", "f", "I", "ConstantValue"] {
+            class.push(1);
+            class.extend((name.len() as u16).to_be_bytes());
+            class.extend(name.as_bytes());
+        }
+        class.extend([7, 0, 1]); // #5: Class A
+        class.extend([15, 6, 0, 6]); // #6: MethodHandle REF_invokeStatic #6
+        class.extend([0, 0x21, 0, 5, 0, 0, 0, 0]);
+        class.extend([0, 1, 0, 0x18, 0, 2, 0, 3, 0, 1, 0, 4, 0, 0, 0, 2, 0, 6]);
+        class.extend([0, 0, 0, 0]);
+        assert!(matches!(
+            ClassFile::parse(&class),
+            Err(ClassFileError::WrongConstantType(6))
+        ));
+    }
+
+    #[test]
+    fn renders_types() {
+        assert_eq!(
+            binary_name("net/minecraft/Util$Inner"),
+            "net.minecraft.Util$Inner"
+        );
+        assert_eq!(
+            render_field_type("[[Ljava/lang/String;"),
+            "java.lang.String[][]"
+        );
+        assert_eq!(
+            render_method_type("(IJ[Z)V"),
+            Some((
+                vec![
+                    "int".to_string(),
+                    "long".to_string(),
+                    "boolean[]".to_string()
+                ],
+                "void".to_string()
+            ))
+        );
+    }
+}
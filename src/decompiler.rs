@@ -1,12 +1,18 @@
+use crate::class_artifacts;
 use crate::colorize::InfoColors;
-use crate::{MojError, MojResult, Version};
+use crate::decompiler_report::DecompilerReport;
+use crate::http::{self, RetryConfig};
+use crate::logging::GRADLE_TARGET;
+use crate::{version_file_name, Config, MojError, MojResult, Version};
 use error_stack::{Report, ResultExt};
 use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::{BufRead, Write};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, Mutex};
 use chrono::{DateTime, Utc};
 
 static PARCHMENT_VERSIONS: LazyLock<LinkedHashMap<&str, &str>> = LazyLock::new(|| {
@@ -59,18 +65,28 @@ pub fn index_parchment_mc_versions(
 #[derive(Debug)]
 pub struct DecompileResult {
     artifacts: HashMap<DecompileArtifact, PathBuf>,
+    report: Option<DecompilerReport>,
 }
 
 impl DecompileResult {
     pub fn artifacts(&self) -> &HashMap<DecompileArtifact, PathBuf> {
         &self.artifacts
     }
+
+    /// The decompiler report, if it was requested.
+    pub fn report(&self) -> Option<&DecompilerReport> {
+        self.report.as_ref()
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum DecompileArtifact {
     DecompiledClasses,
     LibrariesTxt,
+    ClassListings,
+    ApiSignatures,
+    ClassIndex,
+    DecompilerReport,
 }
 
 impl DecompileArtifact {
@@ -78,6 +94,10 @@ impl DecompileArtifact {
         &[
             DecompileArtifact::DecompiledClasses,
             DecompileArtifact::LibrariesTxt,
+            DecompileArtifact::ClassListings,
+            DecompileArtifact::ApiSignatures,
+            DecompileArtifact::ClassIndex,
+            DecompileArtifact::DecompilerReport,
         ]
     }
 
@@ -85,6 +105,10 @@ impl DecompileArtifact {
         match self {
             DecompileArtifact::DecompiledClasses => "decompiled classes",
             DecompileArtifact::LibrariesTxt => "libraries.txt",
+            DecompileArtifact::ClassListings => "class listings",
+            DecompileArtifact::ApiSignatures => "API signatures",
+            DecompileArtifact::ClassIndex => "class hierarchy and reference index",
+            DecompileArtifact::DecompilerReport => "decompiler report",
         }
     }
 
@@ -93,19 +117,54 @@ impl DecompileArtifact {
         match self {
             DecompileArtifact::DecompiledClasses => 5,
             DecompileArtifact::LibrariesTxt => 2,
+            DecompileArtifact::ClassListings => 2,
+            DecompileArtifact::ApiSignatures => 2,
+            DecompileArtifact::ClassIndex => 1,
+            DecompileArtifact::DecompilerReport => 1,
         }
     }
 
-    pub fn path_in_repository(&self) -> &str {
+    pub const fn path_in_repository(&self) -> &'static str {
         match self {
             DecompileArtifact::DecompiledClasses => "src",
             DecompileArtifact::LibrariesTxt => "libraries",
+            DecompileArtifact::ClassListings => "classes",
+            DecompileArtifact::ApiSignatures => "api",
+            DecompileArtifact::ClassIndex => "index",
+            DecompileArtifact::DecompilerReport => "decompiler",
+        }
+    }
+
+    /// The artifact stored at the given path of the repository.
+    pub fn from_path_in_repository(path: &str) -> Option<DecompileArtifact> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|artifact| artifact.path_in_repository() == path)
+    }
+
+    /// The artifact this one is computed from, which means it must be regenerated along with it.
+    pub const fn derived_from(&self) -> Option<DecompileArtifact> {
+        match self {
+            DecompileArtifact::DecompilerReport => Some(DecompileArtifact::DecompiledClasses),
+            _ => None,
         }
     }
+
+    /// Whether the artifact is generated from the Minecraft class files rather than by Gradle.
+    const fn is_from_class_files(&self) -> bool {
+        matches!(
+            self,
+            DecompileArtifact::ClassListings
+                | DecompileArtifact::ApiSignatures
+                | DecompileArtifact::ClassIndex
+        )
+    }
 }
 
 /// Decompiles the given version and returns the path to the decompiled source.
 pub fn decompile_version(
+    config: &Config,
     version: &Version,
     parchment_mc_version: Option<&str>,
     requested_artifacts: &[DecompileArtifact],
@@ -116,7 +175,41 @@ pub fn decompile_version(
         .change_context(MojError::Decompilation)
         .attach("Cannot create decompilation work area")?;
 
-    run_decompile_work(version, parchment_mc_version, requested_artifacts, work_dir)?;
+    let gradle_output = run_decompile_work(
+        config,
+        version,
+        parchment_mc_version,
+        requested_artifacts,
+        work_dir,
+    )?;
+
+    let report = if requested_artifacts.contains(&DecompileArtifact::DecompilerReport) {
+        let report = DecompilerReport::scan(&work_dir.join("decompiledSources"), &gradle_output)?;
+        report.write(&work_dir.join("decompilerReport.txt"))?;
+        Some(report)
+    } else {
+        None
+    };
+
+    if requested_artifacts.iter().any(|a| a.is_from_class_files()) {
+        let classes = class_artifacts::read_classes(&work_dir.join("minecraftJars"))?;
+        for artifact in requested_artifacts {
+            match artifact {
+                DecompileArtifact::ClassListings => class_artifacts::write_class_listings(
+                    &classes,
+                    &work_dir.join("classListings"),
+                    config.include_bytecode,
+                )?,
+                DecompileArtifact::ApiSignatures => {
+                    class_artifacts::write_api_signatures(&classes, &work_dir.join("api.txt"))?
+                }
+                DecompileArtifact::ClassIndex => {
+                    class_artifacts::write_class_index(&classes, &work_dir.join("classIndex"))?
+                }
+                _ => {}
+            }
+        }
+    }
 
     Ok(DecompileResult {
         artifacts: requested_artifacts
@@ -127,39 +220,103 @@ pub fn decompile_version(
                     work_dir.join(match artifact {
                         DecompileArtifact::DecompiledClasses => "decompiledSources",
                         DecompileArtifact::LibrariesTxt => "build/libraries.txt",
+                        DecompileArtifact::ClassListings => "classListings",
+                        DecompileArtifact::ApiSignatures => "api.txt",
+                        DecompileArtifact::ClassIndex => "classIndex",
+                        DecompileArtifact::DecompilerReport => "decompilerReport.txt",
                     }),
                 )
             })
             .collect(),
+        report,
     })
 }
 
 static HAS_STOPPED_DAEMON: AtomicBool = AtomicBool::new(false);
 
+const FABRIC_LOOM_VERSION: &str = "1.15.5";
+const GRADLE_VERSION: &str = "9.4.1";
+/// Loom would pick the Vineflower version itself, so the build scripts pin it to this one.
+const VINEFLOWER_VERSION: &str = "1.11.1";
+const REMAP_JAVA_VERSION: u32 = 21;
+const NOMAP_JAVA_VERSION: u32 = 25;
+/// The date that 26.1-snapshot-1 was released, signaling the start of only unmapped artifacts.
+static NOMAP_START_DATE: LazyLock<DateTime<Utc>> = LazyLock::new(|| {
+    DateTime::parse_from_rfc3339("2025-12-16T12:42:29+00:00")
+        .unwrap()
+        .to_utc()
+});
+
+fn is_unmapped(version: &Version) -> bool {
+    version.release_time >= *NOMAP_START_DATE
+}
+
+/// Everything outside of mojankinator that goes into producing a version's artifacts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Toolchain {
+    pub loom_version: String,
+    pub gradle_version: String,
+    pub java_version: u32,
+    /// The mappings applied to the game, e.g. `mojang+parchment`, or `none` if it is unobfuscated.
+    pub mappings: String,
+    /// The Parchment build, as `<mc version>-<date>`, if Parchment was used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parchment_version: Option<String>,
+    /// The decompiler and its version, e.g. `vineflower 1.11.1`.
+    pub decompiler: String,
+}
+
+impl Toolchain {
+    /// The toolchain used to produce the given version with this build of mojankinator.
+    pub fn current(version: &Version, parchment_mc_version: Option<&str>) -> Self {
+        let unmapped = is_unmapped(version);
+        let parchment_version = parchment_mc_version
+            .filter(|_| !unmapped)
+            .map(|v| format!("{}-{}", v, PARCHMENT_VERSIONS[v]));
+        Self {
+            loom_version: FABRIC_LOOM_VERSION.to_string(),
+            gradle_version: GRADLE_VERSION.to_string(),
+            java_version: if unmapped {
+                NOMAP_JAVA_VERSION
+            } else {
+                REMAP_JAVA_VERSION
+            },
+            mappings: match (unmapped, &parchment_version) {
+                (true, _) => "none",
+                (false, Some(_)) => "mojang+parchment",
+                (false, None) => "mojang",
+            }
+            .to_string(),
+            parchment_version,
+            decompiler: format!("vineflower {}", VINEFLOWER_VERSION),
+        }
+    }
+}
+
 fn get_build_gradle_text(version: &Version) -> String {
     const NOMAP_TEXT: &str = include_str!("./build-nomap.gradle.kts.tmpl");
     const REMAP_TEXT: &str = include_str!("./build-remap.gradle.kts.tmpl");
-    const FABRIC_LOOM_VERSION: &str = "1.15.5";
-    /// The date that 26.1-snapshot-1 was released, signaling the start of only unmapped artifacts.
-    static NOMAP_START_DATE: LazyLock<DateTime<Utc>> = LazyLock::new(||
-        DateTime::parse_from_rfc3339("2025-12-16T12:42:29+00:00").unwrap().to_utc()
-    );
 
-    let text = if version.release_time < *NOMAP_START_DATE {
-        REMAP_TEXT
+    let (text, java_version) = if is_unmapped(version) {
+        (NOMAP_TEXT, NOMAP_JAVA_VERSION)
     } else {
-        NOMAP_TEXT
+        (REMAP_TEXT, REMAP_JAVA_VERSION)
     };
     text.replace("%LOOM_VERSION%", FABRIC_LOOM_VERSION)
+        .replace("%VINEFLOWER_VERSION%", VINEFLOWER_VERSION)
+        .replace("%JAVA_VERSION%", &java_version.to_string())
 }
 
+/// Run the decompilation, returning the output of Gradle.
 fn run_decompile_work(
+    config: &Config,
     version: &Version,
     parchment_mc_version: Option<&str>,
     requested_artifacts: &[DecompileArtifact],
     work_dir: &Path,
-) -> MojResult<()> {
-    let gradle_executable = fetch_gradle(work_dir)?;
+) -> MojResult<Vec<String>> {
+    let gradle_executable = fetch_gradle(work_dir, &config.retry)?;
+    let log = GradleLog::create(version, config.quiet_gradle)?;
 
     std::fs::write(
         work_dir.join("settings.gradle.kts"),
@@ -203,50 +360,226 @@ fn run_decompile_work(
         )
         .is_ok()
     {
-        let status = std::process::Command::new(&gradle_executable)
-            .args(["--stop"])
-            .current_dir(work_dir)
-            .status()
-            .change_context(MojError::Decompilation)
-            .attach("Failed to stop Gradle daemon")
-            .attach_with(|| format!("Gradle executable: {:?}", &gradle_executable))?;
+        let (status, output) = run_gradle(
+            std::process::Command::new(&gradle_executable)
+                .args(["--stop"])
+                .current_dir(work_dir),
+            &log,
+        )
+        .change_context(MojError::Decompilation)
+        .attach("Failed to stop Gradle daemon")
+        .attach_with(|| format!("Gradle executable: {:?}", &gradle_executable))?;
         if !status.success() {
-            return Err(Report::new(MojError::Decompilation).attach("Failed to stop Gradle daemon"));
+            return Err(log.attach_failure(
+                Report::new(MojError::Decompilation).attach("Failed to stop Gradle daemon"),
+                &output,
+            ));
         }
     }
 
     let mut args = vec!["--stacktrace", "--parallel", "--configuration-cache"];
     for artifact in requested_artifacts {
-        match artifact {
-            DecompileArtifact::DecompiledClasses => {
-                args.push("unpackSourcesIntoKnownDir");
-            }
-            DecompileArtifact::LibrariesTxt => {
-                args.push("exportLibraries");
+        let task = match artifact {
+            DecompileArtifact::DecompiledClasses | DecompileArtifact::DecompilerReport => {
+                "unpackSourcesIntoKnownDir"
             }
+            DecompileArtifact::LibrariesTxt => "exportLibraries",
+            DecompileArtifact::ClassListings
+            | DecompileArtifact::ApiSignatures
+            | DecompileArtifact::ClassIndex => "exportMinecraftJars",
+        };
+        if !args.contains(&task) {
+            args.push(task);
         }
     }
 
-    let status = std::process::Command::new(&gradle_executable)
-        .args(args)
-        .current_dir(work_dir)
-        .status()
+    let mut delays = config.retry.delays();
+    let (status, output) = loop {
+        let (status, output) = run_gradle(
+            std::process::Command::new(&gradle_executable)
+                .args(&args)
+                .current_dir(work_dir),
+            &log,
+        )
         .change_context(MojError::Decompilation)
         .attach("Failed to execute decompilation")
         .attach_with(|| format!("Gradle executable: {:?}", &gradle_executable))
         .attach_with(|| format!("Version: {}", version.id))?;
+        if !status.success() && is_network_failure(&output) {
+            if let Some(delay) = delays.next() {
+                log::warn!(
+                    "Gradle failed on a network error for version {}. Retrying in {}s...",
+                    version.id.as_important_value(),
+                    delay.as_secs().as_important_value()
+                );
+                std::thread::sleep(delay);
+                continue;
+            }
+        }
+        break (status, output);
+    };
 
     if status.success() {
-        Ok(())
+        Ok(output)
     } else {
-        Err(Report::new(MojError::Decompilation)
-            .attach("Decompilation failed, see above output for details")
-            .attach(format!("Version: {}", version.id)))
+        Err(log.attach_failure(
+            Report::new(MojError::Decompilation)
+                .attach("Decompilation failed")
+                .attach(format!("Version: {}", version.id)),
+            &output,
+        ))
+    }
+}
+
+/// Explanations of a Gradle failure that show it failed to download something, rather than to
+/// build.
+const NETWORK_FAILURE_SIGNATURES: &[&str] = &[
+    "Could not GET '",
+    "Could not HEAD '",
+    "Could not download ",
+    "Received status code 408",
+    "Received status code 429",
+    "Received status code 5",
+    "java.net.SocketException",
+    "java.net.SocketTimeoutException",
+    "java.net.UnknownHostException",
+    "java.net.ConnectException",
+    "java.net.NoRouteToHostException",
+    "javax.net.ssl.SSLException",
+    "javax.net.ssl.SSLHandshakeException",
+    "Remote host terminated the handshake",
+    "Connection reset",
+    "Read timed out",
+    "Connect timed out",
+    "Temporary failure in name resolution",
+];
+
+/// Whether Gradle failed because of the network. Only its explanations of the failure, under
+/// `* What went wrong:`, are considered, as the build output and stack traces mention the network
+/// for all sorts of reasons. A request the server refused, such as a 404, won't succeed when made
+/// again, so it isn't a network failure.
+fn is_network_failure(output: &[String]) -> bool {
+    let mut explanations = Vec::new();
+    let mut in_explanation = false;
+    for line in output {
+        let line = line.trim();
+        if line == "* What went wrong:" {
+            in_explanation = true;
+        } else if line.starts_with("* ") {
+            // The next section, like `* Try:`
+            in_explanation = false;
+        } else if in_explanation {
+            explanations.push(line);
+        }
     }
+    let refused = explanations.iter().any(|line| {
+        line.split("Received status code ")
+            .skip(1)
+            .filter_map(|rest| rest.get(..3)?.parse::<u16>().ok())
+            .any(|status| (400..500).contains(&status) && status != 408 && status != 429)
+    });
+    !refused
+        && explanations.iter().any(|line| {
+            NETWORK_FAILURE_SIGNATURES
+                .iter()
+                .any(|signature| line.contains(signature))
+        })
+}
+
+/// The per-version log file that all Gradle output is written to.
+struct GradleLog {
+    path: PathBuf,
+    file: Mutex<std::io::BufWriter<std::fs::File>>,
+    /// If set, output only goes to the log file, and to the terminal with `--verbose`.
+    quiet: bool,
 }
 
-fn fetch_gradle(work_dir: &Path) -> MojResult<PathBuf> {
-    const GRADLE_VERSION: &str = "9.4.1";
+impl GradleLog {
+    /// How many lines of output to show when Gradle fails.
+    const FAILURE_TAIL_LINES: usize = 40;
+
+    fn create(version: &Version, quiet: bool) -> MojResult<Self> {
+        let logs_dir = Path::new("./logs");
+        std::fs::create_dir_all(logs_dir)
+            .change_context(MojError::Decompilation)
+            .attach("Cannot create logs directory")?;
+        let path = logs_dir.join(format!("{}.log", version_file_name(&version.id)));
+        let file = std::fs::File::create(&path)
+            .change_context(MojError::Decompilation)
+            .attach("Cannot create Gradle log file")
+            .attach_with(|| format!("Path: {:?}", path))?;
+        Ok(Self {
+            path,
+            file: Mutex::new(std::io::BufWriter::new(file)),
+            quiet,
+        })
+    }
+
+    fn write_line(&self, line: &str) -> std::io::Result<()> {
+        writeln!(self.file.lock().unwrap(), "{}", line)
+    }
+
+    /// Attach the log location and the last lines of output to a Gradle failure.
+    fn attach_failure(&self, report: Report<MojError>, output: &[String]) -> Report<MojError> {
+        let tail = &output[output.len().saturating_sub(Self::FAILURE_TAIL_LINES)..];
+        report
+            .attach(format!("Gradle log: {:?}", self.path))
+            .attach(format!("Last lines of Gradle output:\n{}", tail.join("\n")))
+    }
+}
+
+/// Run Gradle, writing its output to the log and collecting it.
+fn run_gradle(
+    command: &mut std::process::Command,
+    log: &GradleLog,
+) -> std::io::Result<(std::process::ExitStatus, Vec<String>)> {
+    log.write_line(&format!("> {:?}", command))?;
+    let mut child = command
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let forward = |line: &str| {
+        if log.quiet {
+            log::debug!(target: GRADLE_TARGET, "{}", line)
+        } else {
+            log::info!(target: GRADLE_TARGET, "{}", line)
+        }
+    };
+    // Both streams are collected into one list, so the output keeps the order it arrived in
+    let output = Mutex::new(Vec::new());
+    let (stdout_result, stderr_result) = std::thread::scope(|scope| {
+        let stdout = scope.spawn(|| forward_lines(stdout, log, &output, forward));
+        let stderr = scope.spawn(|| forward_lines(stderr, log, &output, forward));
+        (stdout.join().unwrap(), stderr.join().unwrap())
+    });
+    let status = child.wait()?;
+    log.file.lock().unwrap().flush()?;
+    stdout_result?;
+    stderr_result?;
+    Ok((status, output.into_inner().unwrap()))
+}
+
+fn forward_lines(
+    stream: impl std::io::Read,
+    log: &GradleLog,
+    output: &Mutex<Vec<String>>,
+    forward: impl Fn(&str),
+) -> std::io::Result<()> {
+    let mut reader = std::io::BufReader::new(stream);
+    let mut buffer = Vec::new();
+    while reader.read_until(b'\n', &mut buffer)? > 0 {
+        let line = String::from_utf8_lossy(&buffer).trim_end().to_string();
+        log.write_line(&line)?;
+        forward(&line);
+        output.lock().unwrap().push(line);
+        buffer.clear();
+    }
+    Ok(())
+}
+
+fn fetch_gradle(work_dir: &Path, retry: &RetryConfig) -> MojResult<PathBuf> {
     const GRADLE_RELATIVE_PATH: &str = "gradle-install";
     let relative_dir = work_dir.join(GRADLE_RELATIVE_PATH).join(GRADLE_VERSION);
     let gradle_dir = std::path::absolute(&relative_dir)
@@ -255,13 +588,13 @@ fn fetch_gradle(work_dir: &Path) -> MojResult<PathBuf> {
         .attach_with(|| format!("Path: {:?}", &relative_dir))?;
     let gradle_executable = gradle_dir.join("bin/gradle");
     if gradle_executable.exists() {
-        eprintln!(
+        log::debug!(
             "Found Gradle executable at {}",
             gradle_executable.display().as_important_value()
         );
         return Ok(gradle_executable);
     }
-    eprintln!(
+    log::info!(
         "Downloading Gradle {}...",
         GRADLE_VERSION.as_important_value()
     );
@@ -270,17 +603,13 @@ fn fetch_gradle(work_dir: &Path) -> MojResult<PathBuf> {
         .attach("Cannot create Gradle directory")
         .attach_with(|| format!("Path: {:?}", gradle_dir))?;
     let url = format!("https://services.gradle.org/distributions/gradle-{GRADLE_VERSION}-bin.zip");
-    let zip_file_req = ureq::get(&url)
-        .call()
-        .change_context(MojError::Decompilation)
-        .attach("Failed to start Gradle zip download")
-        .attach_with(|| format!("URL: {}", url))?;
     let mut temp_file = tempfile::tempfile()
         .change_context(MojError::Decompilation)
         .attach("Failed to create temporary file for Gradle zip")?;
-    std::io::copy(&mut zip_file_req.into_body().into_reader(), &mut temp_file)
+    http::download(retry, &url, &mut temp_file)
         .change_context(MojError::Decompilation)
-        .attach("Failed to download Gradle zip")?;
+        .attach("Failed to download Gradle zip")
+        .attach_with(|| format!("URL: {}", url))?;
     {
         let mut zip = zip::ZipArchive::new(temp_file)
             .change_context(MojError::Decompilation)
@@ -349,3 +678,63 @@ fn fetch_gradle(work_dir: &Path) -> MojResult<PathBuf> {
     }
     Ok(gradle_executable)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradle_failure(explanation: &[&str]) -> Vec<String> {
+        let mut output = vec![
+            "> Task :genSourcesWithVineflower".to_string(),
+            "java.net.SocketException: logged while building, but not why it failed".to_string(),
+            "FAILURE: Build failed with an exception.".to_string(),
+            String::new(),
+            "* What went wrong:".to_string(),
+        ];
+        output.extend(explanation.iter().map(|line| line.to_string()));
+        output.extend(
+            [
+                "",
+                "* Try:",
+                "> Run with --info or --debug option to get more log output.",
+                "",
+                "* Exception is:",
+                "java.net.SocketTimeoutException: Read timed out",
+            ]
+            .map(str::to_string),
+        );
+        output
+    }
+
+    #[test]
+    fn detects_network_failures_in_the_explanation() {
+        assert!(is_network_failure(&gradle_failure(&[
+            "Execution failed for task ':exportLibraries'.",
+            "> Could not resolve all files for configuration ':minecraftLibraries'.",
+            "   > Could not GET 'https://libraries.minecraft.net/a.jar'. Received status code 503 from server: Service Unavailable",
+        ])));
+        assert!(is_network_failure(&gradle_failure(&[
+            "A problem occurred configuring root project 'work'.",
+            "> Could not HEAD 'https://maven.fabricmc.net/b.pom'. Received status code 429 from server: Too Many Requests",
+        ])));
+        assert!(is_network_failure(&gradle_failure(&[
+            "Could not download minecraft.jar",
+            "> Connection reset",
+        ])));
+    }
+
+    #[test]
+    fn ignores_refused_requests_and_other_output() {
+        assert!(!is_network_failure(&gradle_failure(&[
+            "> Could not GET 'https://maven.fabricmc.net/c.pom'. Received status code 404 from server: Not Found",
+        ])));
+        assert!(!is_network_failure(&gradle_failure(&[
+            "> Could not GET 'https://maven.example.net/d.pom'. Received status code 401 from server: Unauthorized",
+        ])));
+        // The stack trace and the build output mention the network, but the failure doesn't
+        assert!(!is_network_failure(&gradle_failure(&[
+            "Execution failed for task ':genSourcesWithVineflower'.",
+            "> A failure occurred while executing DecompileAction",
+        ])));
+    }
+}
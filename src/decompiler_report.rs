@@ -0,0 +1,231 @@
+use crate::{MojError, MojResult};
+use error_stack::ResultExt;
+use std::fmt::Write;
+use std::path::Path;
+
+/// The marker Vineflower leaves in place of a method body it could not decompile.
+const FAILED_MARKER: &str = "$VF: Couldn't be decompiled";
+/// The prefix of every comment Vineflower leaves about problems in its output.
+const MARKER_PREFIX: &str = "$VF: ";
+/// The severity prefixes Vineflower's logger puts in front of its messages, padded to the same
+/// width. Other tools print `ERROR: ` too, so they only count in the output of the decompile task.
+const LOG_WARNING_PREFIXES: [&str; 2] = ["WARN:  ", "ERROR: "];
+/// The header Gradle prints before the output of the task that runs Vineflower, and again whenever
+/// that output resumes after another task's.
+const DECOMPILE_TASK_HEADER: &str = "> Task :genSourcesWithVineflower";
+
+/// A method that Vineflower flagged in its output.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FlaggedMethod {
+    /// The source file, relative to the decompiled sources root.
+    pub file: String,
+    /// The declaration of the method, or of the closest enclosing declaration if it couldn't be
+    /// determined.
+    pub declaration: String,
+    /// The marker comment, without the `$VF: ` prefix.
+    pub marker: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DecompilerReport {
+    /// Methods that could not be decompiled at all.
+    pub failed: Vec<FlaggedMethod>,
+    /// Methods that were decompiled, but with workarounds or caveats.
+    pub partial: Vec<FlaggedMethod>,
+    /// Warnings from the decompiler in the Gradle output.
+    pub log_warnings: Vec<String>,
+}
+
+impl DecompilerReport {
+    /// Scan the decompiled sources for Vineflower markers and the Gradle output for warnings.
+    pub fn scan(sources_dir: &Path, gradle_output: &[String]) -> MojResult<Self> {
+        let mut report = DecompilerReport::default();
+        for entry in walkdir::WalkDir::new(sources_dir) {
+            let entry = entry.change_context(MojError::Decompilation)?;
+            if !entry.file_type().is_file() || entry.path().extension() != Some("java".as_ref()) {
+                continue;
+            }
+            let contents = std::fs::read(entry.path())
+                .change_context(MojError::Decompilation)
+                .attach("Failed to read decompiled source")
+                .attach_with(|| format!("Path: {:?}", entry.path()))?;
+            let contents = String::from_utf8_lossy(&contents);
+            if !contents.contains(MARKER_PREFIX) {
+                continue;
+            }
+            let file = entry
+                .path()
+                .strip_prefix(sources_dir)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            report.scan_source(&file, &contents);
+        }
+        report.log_warnings = decompiler_log_warnings(gradle_output);
+
+        report.failed.sort();
+        report.failed.dedup();
+        report.partial.sort();
+        report.partial.dedup();
+        Ok(report)
+    }
+
+    fn scan_source(&mut self, file: &str, contents: &str) {
+        let lines: Vec<&str> = contents.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            let Some(start) = line.find(MARKER_PREFIX) else {
+                continue;
+            };
+            let flagged = FlaggedMethod {
+                file: file.to_string(),
+                declaration: find_declaration(&lines, i),
+                marker: line[start + MARKER_PREFIX.len()..].trim().to_string(),
+            };
+            if line.contains(FAILED_MARKER) {
+                self.failed.push(flagged);
+            } else {
+                self.partial.push(flagged);
+            }
+        }
+    }
+
+    /// A one-line summary of the report.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} failed methods, {} partially decompiled methods, {} log warnings",
+            self.failed.len(),
+            self.partial.len(),
+            self.log_warnings.len()
+        )
+    }
+
+    pub fn write(&self, path: &Path) -> MojResult<()> {
+        let mut out = String::new();
+        writeln!(out, "Failed methods: {}", self.failed.len()).unwrap();
+        writeln!(out, "Partially decompiled methods: {}", self.partial.len()).unwrap();
+        writeln!(out, "Decompiler log warnings: {}", self.log_warnings.len()).unwrap();
+        for (title, methods) in [("Failed", &self.failed), ("Partial", &self.partial)] {
+            writeln!(out, "\n== {} ==", title).unwrap();
+            for method in methods {
+                writeln!(
+                    out,
+                    "{}: {}: {}",
+                    method.file, method.declaration, method.marker
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "\n== Decompiler log warnings ==").unwrap();
+        for warning in &self.log_warnings {
+            writeln!(out, "{}", warning).unwrap();
+        }
+        std::fs::write(path, out)
+            .change_context(MojError::Decompilation)
+            .attach("Failed to write decompiler report")
+            .attach_with(|| format!("Path: {:?}", path))
+    }
+}
+
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("//") || line.starts_with("/*") || line.starts_with('*')
+}
+
+fn looks_like_declaration(line: &str) -> bool {
+    let line = line.trim();
+    !is_comment(line) && line.contains('(') && (line.ends_with('{') || line.ends_with(';'))
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The warnings and errors Vineflower logged, out of the lines of Gradle output the decompile task
+/// printed. The output of a task ends where another task's or the build result starts.
+fn decompiler_log_warnings(gradle_output: &[String]) -> Vec<String> {
+    let mut in_decompile_task = false;
+    let mut warnings = Vec::new();
+    for line in gradle_output {
+        if line.starts_with("> Task ")
+            || line.starts_with("BUILD ")
+            || line.starts_with("FAILURE: ")
+        {
+            in_decompile_task = line.trim_end() == DECOMPILE_TASK_HEADER;
+        } else if in_decompile_task && is_decompiler_warning(line) {
+            warnings.push(line.trim().to_string());
+        }
+    }
+    warnings
+}
+
+/// Whether a line of Gradle output is a warning or error logged by Vineflower. Its messages are
+/// indented to show nesting, so the prefix can follow some whitespace.
+fn is_decompiler_warning(line: &str) -> bool {
+    let line = line.trim_start();
+    LOG_WARNING_PREFIXES
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// Find the declaration a marker at the given line applies to. Vineflower puts markers either
+/// directly above a method or at the start of its body.
+fn find_declaration(lines: &[&str], marker_line: usize) -> String {
+    let following = lines[marker_line + 1..]
+        .iter()
+        .find(|line| !is_comment(line) && !line.trim().is_empty());
+    if let Some(line) = following.filter(|line| {
+        looks_like_declaration(line) && indentation(line) <= indentation(lines[marker_line])
+    }) {
+        return line.trim().trim_end_matches('{').trim().to_string();
+    }
+    let marker_indent = indentation(lines[marker_line]);
+    lines[..marker_line]
+        .iter()
+        .rev()
+        .find(|line| indentation(line) < marker_indent && looks_like_declaration(line))
+        .map(|line| line.trim().trim_end_matches('{').trim().to_string())
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_vineflower_lines_are_warnings() {
+        assert!(is_decompiler_warning(
+            "WARN:  Method a.b.C.foo ()V couldn't be decompiled."
+        ));
+        assert!(is_decompiler_warning("\t\tERROR: Unexpected exception"));
+        assert!(!is_decompiler_warning("ERROR:Unable to resolve dependency"));
+        assert!(!is_decompiler_warning(
+            "> Task :genSourcesWithVineflower WARN: deprecated"
+        ));
+        assert!(!is_decompiler_warning("INFO:  Decompiling class a.b.C"));
+    }
+
+    #[test]
+    fn only_the_decompile_task_logs_warnings() {
+        let output = [
+            "> Task :exportLibraries",
+            "ERROR: Failed to resolve a library, using a fallback",
+            "> Task :genSourcesWithVineflower",
+            "INFO:  Decompiling class a.b.C",
+            "WARN:  Method a.b.C.foo ()V couldn't be decompiled.",
+            "> Task :exportMinecraftJars",
+            "ERROR: Not from Vineflower",
+            "> Task :genSourcesWithVineflower",
+            "\tERROR: Unexpected exception",
+            "BUILD SUCCESSFUL in 2m",
+            "ERROR: After the build",
+        ]
+        .map(str::to_string);
+        assert_eq!(
+            decompiler_log_warnings(&output),
+            [
+                "WARN:  Method a.b.C.foo ()V couldn't be decompiled.",
+                "ERROR: Unexpected exception"
+            ]
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use crate::classfile::{
+    binary_name, class_keyword, class_modifiers, field_modifiers, method_modifiers,
+    render_field_type, render_method_type, ClassFile, ClassFileResult, Code, Constant, Member,
+};
+use std::fmt::Write;
+
+/// Mnemonics for opcodes `0x00..=0xC9`, indexed by opcode.
+#[rustfmt::skip]
+static MNEMONICS: [&str; 202] = [
+    "nop", "aconst_null", "iconst_m1", "iconst_0", "iconst_1", "iconst_2", "iconst_3", "iconst_4",
+    "iconst_5", "lconst_0", "lconst_1", "fconst_0", "fconst_1", "fconst_2", "dconst_0", "dconst_1",
+    "bipush", "sipush", "ldc", "ldc_w", "ldc2_w", "iload", "lload", "fload", "dload", "aload",
+    "iload_0", "iload_1", "iload_2", "iload_3", "lload_0", "lload_1", "lload_2", "lload_3",
+    "fload_0", "fload_1", "fload_2", "fload_3", "dload_0", "dload_1", "dload_2", "dload_3",
+    "aload_0", "aload_1", "aload_2", "aload_3", "iaload", "laload", "faload", "daload", "aaload",
+    "baload", "caload", "saload", "istore", "lstore", "fstore", "dstore", "astore", "istore_0",
+    "istore_1", "istore_2", "istore_3", "lstore_0", "lstore_1", "lstore_2", "lstore_3", "fstore_0",
+    "fstore_1", "fstore_2", "fstore_3", "dstore_0", "dstore_1", "dstore_2", "dstore_3", "astore_0",
+    "astore_1", "astore_2", "astore_3", "iastore", "lastore", "fastore", "dastore", "aastore",
+    "bastore", "castore", "sastore", "pop", "pop2", "dup", "dup_x1", "dup_x2", "dup2", "dup2_x1",
+    "dup2_x2", "swap", "iadd", "ladd", "fadd", "dadd", "isub", "lsub", "fsub", "dsub", "imul",
+    "lmul", "fmul", "dmul", "idiv", "ldiv", "fdiv", "ddiv", "irem", "lrem", "frem", "drem", "ineg",
+    "lneg", "fneg", "dneg", "ishl", "lshl", "ishr", "lshr", "iushr", "lushr", "iand", "land", "ior",
+    "lor", "ixor", "lxor", "iinc", "i2l", "i2f", "i2d", "l2i", "l2f", "l2d", "f2i", "f2l", "f2d",
+    "d2i", "d2l", "d2f", "i2b", "i2c", "i2s", "lcmp", "fcmpl", "fcmpg", "dcmpl", "dcmpg", "ifeq",
+    "ifne", "iflt", "ifge", "ifgt", "ifle", "if_icmpeq", "if_icmpne", "if_icmplt", "if_icmpge",
+    "if_icmpgt", "if_icmple", "if_acmpeq", "if_acmpne", "goto", "jsr", "ret", "tableswitch",
+    "lookupswitch", "ireturn", "lreturn", "freturn", "dreturn", "areturn", "return", "getstatic",
+    "putstatic", "getfield", "putfield", "invokevirtual", "invokespecial", "invokestatic",
+    "invokeinterface", "invokedynamic", "new", "newarray", "anewarray", "arraylength", "athrow",
+    "checkcast", "instanceof", "monitorenter", "monitorexit", "wide", "multianewarray", "ifnull",
+    "ifnonnull", "goto_w", "jsr_w",
+];
+
+/// Render a javap-style listing of the class. The listing intentionally leaves out anything that
+/// depends on constant pool layout, so that it only changes when the class itself does.
+pub fn render_class_listing(class: &ClassFile, include_bytecode: bool) -> ClassFileResult<String> {
+    let mut out = render_class_declaration(class);
+    out.push('\n');
+    writeln!(
+        out,
+        "  class version: {}.{}",
+        class.major_version, class.minor_version
+    )
+    .unwrap();
+    if let Some(signature) = &class.signature {
+        writeln!(out, "  signature: {}", signature).unwrap();
+    }
+    out.push_str("{\n");
+
+    for field in &class.fields {
+        let mut declaration = field_modifiers(field.access_flags).join(" ");
+        if !declaration.is_empty() {
+            declaration.push(' ');
+        }
+        writeln!(
+            out,
+            "  {}{} {};",
+            declaration,
+            render_field_type(&field.descriptor),
+            field.name
+        )
+        .unwrap();
+        write_member_details(&mut out, field);
+        if let Some(value) = &field.constant_value {
+            writeln!(out, "    constant value: {}", value).unwrap();
+        }
+        out.push('\n');
+    }
+
+    for method in &class.methods {
+        writeln!(out, "  {};", render_method_declaration(class, method)).unwrap();
+        write_member_details(&mut out, method);
+        if include_bytecode {
+            if let Some(code) = &method.code {
+                write_code(&mut out, class, code)?;
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Render the class the way Java would declare it, e.g. `public final class a.b.C extends a.b.D`.
+pub fn render_class_declaration(class: &ClassFile) -> String {
+    let flags = class.declared_access_flags();
+    let mut header = class_modifiers(flags);
+    header.push(class_keyword(flags));
+    let mut out = format!("{} {}", header.join(" "), binary_name(&class.this_class));
+    // Interfaces always have `Object` as their superclass, which isn't worth mentioning
+    if let Some(super_class) = class.super_class.as_ref().filter(|_| !class.is_interface()) {
+        write!(out, " extends {}", binary_name(super_class)).unwrap();
+    }
+    if !class.interfaces.is_empty() {
+        let interfaces: Vec<_> = class.interfaces.iter().map(|i| binary_name(i)).collect();
+        write!(
+            out,
+            " {} {}",
+            if class.is_interface() {
+                "extends"
+            } else {
+                "implements"
+            },
+            interfaces.join(", ")
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Render a method the way Java would declare it, e.g. `public static int foo(long)`.
+pub fn render_method_declaration(class: &ClassFile, method: &Member) -> String {
+    if method.name == "<clinit>" {
+        return "static {}".to_string();
+    }
+    let mut declaration = method_modifiers(method.access_flags);
+    let (params, return_type) = render_method_type(&method.descriptor)
+        .unwrap_or_else(|| (vec![method.descriptor.clone()], String::new()));
+    let name = match method.name.as_str() {
+        "<init>" => binary_name(&class.this_class),
+        _ => format!("{} {}", return_type, method.name),
+    };
+    let mut rendered = format!("{}({})", name, params.join(", "));
+    if !method.exceptions.is_empty() {
+        let exceptions: Vec<_> = method.exceptions.iter().map(|e| binary_name(e)).collect();
+        write!(rendered, " throws {}", exceptions.join(", ")).unwrap();
+    }
+    declaration.push(&rendered);
+    declaration.join(" ")
+}
+
+fn write_member_details(out: &mut String, member: &Member) {
+    writeln!(out, "    descriptor: {}", member.descriptor).unwrap();
+    if let Some(signature) = &member.signature {
+        writeln!(out, "    signature: {}", signature).unwrap();
+    }
+}
+
+fn write_code(out: &mut String, class: &ClassFile, code: &Code) -> ClassFileResult<()> {
+    writeln!(out, "    Code:").unwrap();
+    writeln!(
+        out,
+        "      stack={}, locals={}",
+        code.max_stack, code.max_locals
+    )
+    .unwrap();
+    for (offset, instruction) in disassemble(class, &code.bytecode)? {
+        writeln!(out, "      {:>5}: {}", offset, instruction).unwrap();
+    }
+    if !code.exception_table.is_empty() {
+        writeln!(out, "    Exception table:").unwrap();
+        for handler in &code.exception_table {
+            writeln!(
+                out,
+                "      {} - {} -> {} {}",
+                handler.start_pc,
+                handler.end_pc,
+                handler.handler_pc,
+                handler
+                    .catch_type
+                    .as_deref()
+                    .map(binary_name)
+                    .unwrap_or_else(|| "any".to_string())
+            )
+            .unwrap();
+        }
+    }
+    Ok(())
+}
+
+struct BytecodeReader<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl BytecodeReader<'_> {
+    fn u1(&mut self) -> u8 {
+        let value = self.code.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        value
+    }
+
+    fn u2(&mut self) -> u16 {
+        u16::from_be_bytes([self.u1(), self.u1()])
+    }
+
+    fn i4(&mut self) -> i32 {
+        i32::from_be_bytes([self.u1(), self.u1(), self.u1(), self.u1()])
+    }
+}
+
+/// How many bytes of code are left after the reader's position.
+fn remaining(reader: &BytecodeReader) -> i64 {
+    reader.code.len().saturating_sub(reader.pos) as i64
+}
+
+/// Decode the instructions of a method, rendering each with its resolved operands.
+fn disassemble(class: &ClassFile, code: &[u8]) -> ClassFileResult<Vec<(usize, String)>> {
+    let pool = &class.constant_pool;
+    let mut reader = BytecodeReader { code, pos: 0 };
+    let mut instructions = Vec::new();
+    while reader.pos < code.len() {
+        let offset = reader.pos;
+        let opcode = reader.u1();
+        let Some(mnemonic) = MNEMONICS.get(opcode as usize) else {
+            instructions.push((offset, format!("<unknown opcode {:#04x}>", opcode)));
+            continue;
+        };
+        let branch = |delta: i32| (offset as i64 + delta as i64).to_string();
+        let operands = match opcode {
+            // bipush
+            0x10 => (reader.u1() as i8).to_string(),
+            // sipush
+            0x11 => (reader.u2() as i16).to_string(),
+            // ldc
+            0x12 => render_dynamic(class, reader.u1() as u16)?,
+            // ldc_w, ldc2_w
+            0x13 | 0x14 => render_dynamic(class, reader.u2())?,
+            // field and method instructions, new, anewarray, checkcast, instanceof
+            0xB2..=0xB8 | 0xBB | 0xBD | 0xC0 | 0xC1 => pool.display(reader.u2())?,
+            // local variable loads and stores, ret
+            0x15..=0x19 | 0x36..=0x3A | 0xA9 => reader.u1().to_string(),
+            // iinc
+            0x84 => format!("{}, {}", reader.u1(), reader.u1() as i8),
+            // conditional branches, goto, jsr, ifnull, ifnonnull
+            0x99..=0xA8 | 0xC6 | 0xC7 => branch(reader.u2() as i16 as i32),
+            // goto_w, jsr_w
+            0xC8 | 0xC9 => branch(reader.i4()),
+            // tableswitch
+            0xAA => {
+                reader.pos = (reader.pos + 3) & !3;
+                let default = reader.i4();
+                let low = reader.i4();
+                let high = reader.i4();
+                // Every case takes 4 bytes, so a range that doesn't fit in the rest of the code is
+                // malformed, and would otherwise take billions of iterations
+                let cases = i64::from(high) - i64::from(low) + 1;
+                if cases < 1 || cases * 4 > remaining(&reader) {
+                    instructions.push((offset, "<malformed tableswitch>".to_string()));
+                    break;
+                }
+                let mut cases = Vec::new();
+                for key in low..=high {
+                    cases.push(format!("{}: {}", key, branch(reader.i4())));
+                }
+                cases.push(format!("default: {}", branch(default)));
+                format!("{{ {} }}", cases.join(", "))
+            }
+            // lookupswitch
+            0xAB => {
+                reader.pos = (reader.pos + 3) & !3;
+                let default = reader.i4();
+                let pairs = reader.i4();
+                if pairs < 0 || i64::from(pairs) * 8 > remaining(&reader) {
+                    instructions.push((offset, "<malformed lookupswitch>".to_string()));
+                    break;
+                }
+                let mut cases = Vec::new();
+                for _ in 0..pairs {
+                    let key = reader.i4();
+                    cases.push(format!("{}: {}", key, branch(reader.i4())));
+                }
+                cases.push(format!("default: {}", branch(default)));
+                format!("{{ {} }}", cases.join(", "))
+            }
+            // invokeinterface
+            0xB9 => {
+                let operand = pool.display(reader.u2())?;
+                // count and a zero byte, both derivable from the descriptor
+                reader.u2();
+                operand
+            }
+            // invokedynamic
+            0xBA => {
+                let index = reader.u2();
+                reader.u2();
+                render_dynamic(class, index)?
+            }
+            // newarray
+            0xBC => match reader.u1() {
+                4 => "boolean",
+                5 => "char",
+                6 => "float",
+                7 => "double",
+                8 => "byte",
+                9 => "short",
+                10 => "int",
+                11 => "long",
+                _ => "<unknown>",
+            }
+            .to_string(),
+            // wide
+            0xC4 => {
+                let modified = reader.u1();
+                let name = MNEMONICS.get(modified as usize).unwrap_or(&"<unknown>");
+                if modified == 0x84 {
+                    format!("{} {}, {}", name, reader.u2(), reader.u2() as i16)
+                } else {
+                    format!("{} {}", name, reader.u2())
+                }
+            }
+            // multianewarray
+            0xC5 => format!("{}, {}", pool.display(reader.u2())?, reader.u1()),
+            _ => String::new(),
+        };
+        instructions.push((
+            offset,
+            if operands.is_empty() {
+                mnemonic.to_string()
+            } else {
+                format!("{} {}", mnemonic, operands)
+            },
+        ));
+    }
+    Ok(instructions)
+}
+
+/// Render a constant, resolving the bootstrap method of dynamic constants and call sites, which
+/// the constant pool alone can't.
+fn render_dynamic(class: &ClassFile, index: u16) -> ClassFileResult<String> {
+    let pool = &class.constant_pool;
+    let (kind, bootstrap, nat) = match pool.get(index)? {
+        Constant::Dynamic(bootstrap, nat) => ("Dynamic", bootstrap, nat),
+        Constant::InvokeDynamic(bootstrap, nat) => ("InvokeDynamic", bootstrap, nat),
+        _ => return pool.display(index),
+    };
+    let (name, descriptor) = pool.name_and_type(*nat)?;
+    let mut rendered = format!("{} {}:{}", kind, name, descriptor);
+    if let Some(bootstrap) = class.bootstrap_methods.get(*bootstrap as usize) {
+        write!(
+            rendered,
+            " bootstrap {}",
+            pool.display(bootstrap.method_ref)?
+        )
+        .unwrap();
+        let arguments = bootstrap
+            .arguments
+            .iter()
+            .map(|&argument| pool.display(argument))
+            .collect::<ClassFileResult<Vec<_>>>()?;
+        if !arguments.is_empty() {
+            write!(rendered, " [{}]", arguments.join(", ")).unwrap();
+        }
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/classes/fixture/Fixture.class");
+
+    fn listing() -> String {
+        let class = ClassFile::parse(FIXTURE).unwrap();
+        render_class_listing(&class, true).unwrap()
+    }
+
+    #[test]
+    fn renders_declarations() {
+        let listing = listing();
+        assert!(listing.starts_with(
+            "public class fixture.Fixture extends java.lang.Object implements java.lang.Comparable\n"
+        ));
+        assert!(listing.contains("\n  public static final int ANSWER;\n"));
+        assert!(listing.contains(
+            "\n  protected java.lang.String greet(java.lang.String) throws java.io.IOException;\n"
+        ));
+        assert!(listing.contains("\n  public bridge synthetic int compareTo(java.lang.Object);\n"));
+    }
+
+    #[test]
+    fn renders_switches() {
+        let listing = listing();
+        assert!(listing.contains("tableswitch { 1: 28, 2: 31, 3: 34, default: 37 }"));
+        assert!(listing.contains("lookupswitch { 1: 36, 1000: 39, 100000: 42, default: 45 }"));
+    }
+
+    #[test]
+    fn resolves_bootstrap_methods() {
+        let listing = listing();
+        assert!(listing.contains(
+            "invokedynamic InvokeDynamic getAsInt:()Ljava/util/function/IntSupplier; bootstrap \
+             MethodHandle REF_invokeStatic Method java/lang/invoke/LambdaMetafactory.metafactory:"
+        ));
+        assert!(listing.contains("[String \"Hello \\u{1} from \\u{1}\"]"));
+    }
+
+    #[test]
+    fn leaves_out_constant_pool_indices() {
+        assert!(!listing().contains('#'));
+    }
+
+    #[test]
+    fn stops_at_malformed_switches() {
+        let class = ClassFile::parse(FIXTURE).unwrap();
+        // A tableswitch claiming 2^31 cases in 16 bytes of code
+        let code = [
+            0xAA, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x7F, 0xFF, 0xFF, 0xFF,
+        ];
+        assert_eq!(
+            disassemble(&class, &code).unwrap(),
+            [(0, "<malformed tableswitch>".to_string())]
+        );
+        let code = [0xAB, 0, 0, 0, 0, 0, 0, 0, 0x7F, 0xFF, 0xFF, 0xFF];
+        assert_eq!(
+            disassemble(&class, &code).unwrap(),
+            [(0, "<malformed lookupswitch>".to_string())]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use crate::colorize::InfoColors;
+use crate::decompiler::DecompileArtifact;
+use crate::{open_repository, version_file_name, Config, MojError, MojResult};
+use chrono::{DateTime, Datelike, Timelike};
+use error_stack::{Report, ResultExt};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// A plain directory.
+    Dir,
+    Zip,
+    /// A tarball compressed with zstd.
+    #[value(name = "tar.zst")]
+    TarZst,
+}
+
+impl ExportFormat {
+    const fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Dir => "",
+            ExportFormat::Zip => ".zip",
+            ExportFormat::TarZst => ".tar.zst",
+        }
+    }
+}
+
+/// Where the files of an export are written to.
+enum ExportWriter {
+    Dir(PathBuf),
+    Zip(Box<zip::ZipWriter<File>>, zip::write::SimpleFileOptions),
+    TarZst(tar::Builder<zstd::Encoder<'static, File>>, u64),
+}
+
+impl ExportWriter {
+    /// The zstd level to compress tarballs with. Higher levels take much longer for little gain.
+    const ZSTD_LEVEL: i32 = 9;
+
+    /// Create the writer. Every file gets the given modification time, so exporting the same
+    /// version twice produces the same archive.
+    fn create(format: ExportFormat, output: &Path, mtime: i64) -> MojResult<Self> {
+        let create_file = || {
+            File::create(output)
+                .change_context(MojError::Export)
+                .attach_with(|| format!("Path: {:?}", output))
+        };
+        Ok(match format {
+            ExportFormat::Dir => {
+                std::fs::create_dir_all(output)
+                    .change_context(MojError::Export)
+                    .attach_with(|| format!("Path: {:?}", output))?;
+                ExportWriter::Dir(output.to_path_buf())
+            }
+            ExportFormat::Zip => {
+                // Zip timestamps can't represent anything before 1980
+                let time = DateTime::from_timestamp(mtime, 0)
+                    .and_then(|time| {
+                        zip::DateTime::from_date_and_time(
+                            time.year().try_into().ok()?,
+                            time.month() as u8,
+                            time.day() as u8,
+                            time.hour() as u8,
+                            time.minute() as u8,
+                            time.second() as u8,
+                        )
+                        .ok()
+                    })
+                    .unwrap_or_default();
+                let options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .last_modified_time(time);
+                ExportWriter::Zip(Box::new(zip::ZipWriter::new(create_file()?)), options)
+            }
+            ExportFormat::TarZst => {
+                let encoder = zstd::Encoder::new(create_file()?, Self::ZSTD_LEVEL)
+                    .change_context(MojError::Export)
+                    .attach_with(|| format!("Path: {:?}", output))?;
+                ExportWriter::TarZst(tar::Builder::new(encoder), mtime.max(0) as u64)
+            }
+        })
+    }
+
+    fn add(&mut self, path: &str, mode: i32, contents: &[u8]) -> MojResult<()> {
+        let permissions = if mode == i32::from(git2::FileMode::BlobExecutable) {
+            0o755
+        } else {
+            0o644
+        };
+        match self {
+            ExportWriter::Dir(root) => {
+                let file = root.join(path);
+                std::fs::create_dir_all(file.parent().unwrap())
+                    .change_context(MojError::Export)
+                    .attach_with(|| format!("Path: {:?}", file))?;
+                std::fs::write(&file, contents)
+                    .change_context(MojError::Export)
+                    .attach_with(|| format!("Path: {:?}", file))?;
+                if permissions != 0o644 {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(permissions))
+                        .change_context(MojError::Export)
+                        .attach_with(|| format!("Path: {:?}", file))?;
+                }
+            }
+            ExportWriter::Zip(zip, options) => {
+                zip.start_file(path, options.unix_permissions(permissions))
+                    .change_context(MojError::Export)
+                    .attach_with(|| format!("Path: {}", path))?;
+                zip.write_all(contents)
+                    .change_context(MojError::Export)
+                    .attach_with(|| format!("Path: {}", path))?;
+            }
+            ExportWriter::TarZst(tar, mtime) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(contents.len() as u64);
+                header.set_mode(permissions);
+                header.set_mtime(*mtime);
+                header.set_entry_type(tar::EntryType::Regular);
+                tar.append_data(&mut header, path, contents)
+                    .change_context(MojError::Export)
+                    .attach_with(|| format!("Path: {}", path))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> MojResult<()> {
+        match self {
+            ExportWriter::Dir(_) => {}
+            ExportWriter::Zip(zip, _) => {
+                zip.finish().change_context(MojError::Export)?;
+            }
+            ExportWriter::TarZst(tar, _) => {
+                tar.into_inner()
+                    .and_then(|encoder| encoder.finish())
+                    .change_context(MojError::Export)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the files of a version out of the repository, limited to the given artifacts if there
+/// are any.
+pub fn export(
+    config: &Config,
+    version_id: &str,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+    artifacts: &[DecompileArtifact],
+) -> MojResult<()> {
+    let repo = open_repository(config)?;
+    let mtime = repo.version_release_time(version_id)?;
+    let output = output.unwrap_or_else(|| {
+        Path::new("./exports").join(format!(
+            "{}{}",
+            version_file_name(version_id),
+            format.extension()
+        ))
+    });
+    if output.exists() {
+        return Err(Report::new(MojError::UserError)
+            .attach(format!("{:?} already exists", output))
+            .attach("Remove it or choose another output path"));
+    }
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)
+            .change_context(MojError::Export)
+            .attach_with(|| format!("Path: {:?}", parent))?;
+    }
+
+    log::info!(
+        "Exporting {} to {}...",
+        version_id.as_important_value(),
+        output.display().as_important_value()
+    );
+    let mut writer = ExportWriter::create(format, &output, mtime)?;
+    let paths: Vec<_> = artifacts.iter().map(|a| a.path_in_repository()).collect();
+    let mut files = 0;
+    repo.for_each_version_file(version_id, &paths, |path, mode, contents| {
+        files += 1;
+        writer.add(path, mode, contents)
+    })?;
+    writer.finish()?;
+    log::info!("Exported {} files", files.as_important_value());
+    Ok(())
+}
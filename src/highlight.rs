@@ -0,0 +1,210 @@
+use crate::html::escape_html;
+
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "permits",
+    "private",
+    "protected",
+    "public",
+    "record",
+    "return",
+    "sealed",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "var",
+    "void",
+    "volatile",
+    "while",
+    "yield",
+];
+
+/// Keywords that are only reserved in some places, and can otherwise be used as names.
+const CONTEXTUAL_KEYWORDS: &[&str] = &["permits", "record", "sealed", "var", "yield"];
+
+/// The CSS class of a highlighted token.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Token {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+    Annotation,
+}
+
+impl Token {
+    const fn class(&self) -> Option<&'static str> {
+        match self {
+            Token::Plain => None,
+            Token::Keyword => Some("k"),
+            Token::String => Some("s"),
+            Token::Comment => Some("c"),
+            Token::Number => Some("n"),
+            Token::Annotation => Some("a"),
+        }
+    }
+}
+
+/// Whether a file is highlighted as Java.
+pub fn is_java(path: &str) -> bool {
+    path.ends_with(".java")
+}
+
+/// Render a file as one line of HTML per line of the file. Java sources are highlighted, anything
+/// else is only escaped.
+pub fn highlight(path: &str, source: &str) -> Vec<String> {
+    if !is_java(path) {
+        return source.lines().map(escape_html).collect();
+    }
+    let mut lines = vec![String::new()];
+    for (token, text) in tokenize_java(source) {
+        // Comments and text blocks can span lines, so their spans are closed at each line end
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            let part = part.strip_suffix('\r').unwrap_or(part);
+            if part.is_empty() {
+                continue;
+            }
+            let line = lines.last_mut().unwrap();
+            match token.class() {
+                Some(class) => line.push_str(&format!(
+                    "<span class=\"{}\">{}</span>",
+                    class,
+                    escape_html(part)
+                )),
+                None => line.push_str(&escape_html(part)),
+            }
+        }
+    }
+    // Match `str::lines`, which doesn't count a final line ending as the start of another line
+    if source.ends_with('\n') {
+        lines.pop();
+    }
+    lines
+}
+
+/// Split a Java source into tokens. This is not a full lexer, just enough to colour decompiled code.
+fn tokenize_java(source: &str) -> Vec<(Token, &str)> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut plain_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &source[i..];
+        let (token, len) = if rest.starts_with("//") {
+            (Token::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            (
+                Token::Comment,
+                comment.find("*/").map_or(rest.len(), |end| end + 4),
+            )
+        } else if rest.starts_with("\"\"\"") {
+            (Token::String, quoted_len(rest, "\"\"\"", false))
+        } else if rest.starts_with('"') {
+            (Token::String, quoted_len(rest, "\"", true))
+        } else if rest.starts_with('\'') {
+            (Token::String, quoted_len(rest, "'", true))
+        } else if bytes[i] == b'@' && rest[1..].starts_with(is_identifier_start) {
+            (Token::Annotation, 1 + identifier_len(&rest[1..]))
+        } else if bytes[i].is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            (Token::Number, len)
+        } else if rest.starts_with(is_identifier_start) {
+            let len = identifier_len(rest);
+            let word = &rest[..len];
+            // Contextual keywords are also valid names, as in `builder.record()`
+            let is_member = source[..i].ends_with('.');
+            if KEYWORDS.contains(&word) && !(is_member && CONTEXTUAL_KEYWORDS.contains(&word)) {
+                (Token::Keyword, len)
+            } else {
+                i += len;
+                continue;
+            }
+        } else {
+            i += rest.chars().next().unwrap().len_utf8();
+            continue;
+        };
+        if plain_start < i {
+            tokens.push((Token::Plain, &source[plain_start..i]));
+        }
+        tokens.push((token, &rest[..len]));
+        i += len;
+        plain_start = i;
+    }
+    if plain_start < bytes.len() {
+        tokens.push((Token::Plain, &source[plain_start..]));
+    }
+    tokens
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+fn identifier_len(text: &str) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .unwrap_or(text.len())
+}
+
+/// The length of a literal starting with `quote`, including both quotes. Unterminated literals end
+/// at the end of the line if `single_line`, or at the end of the source.
+fn quoted_len(text: &str, quote: &str, single_line: bool) -> usize {
+    let mut chars = text.char_indices().skip(quote.len());
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if single_line && c == '\n' {
+            return i;
+        } else if text[i..].starts_with(quote) {
+            return i + quote.len();
+        }
+    }
+    text.len()
+}
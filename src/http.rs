@@ -0,0 +1,114 @@
+use crate::colorize::InfoColors;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Seek;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Shared by every request, so connections to the same host are reused.
+static AGENT: LazyLock<ureq::Agent> = LazyLock::new(ureq::Agent::new_with_defaults);
+
+/// How often and how patiently to retry work that failed because of the network.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// How many times to retry after the first attempt fails.
+    pub retries: u32,
+    /// The delay before the first retry, doubled before each following one.
+    pub initial_delay_secs: u64,
+    /// The longest delay between two attempts.
+    pub max_delay_secs: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            initial_delay_secs: 2,
+            max_delay_secs: 60,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The delay before each retry, one per retry.
+    pub fn delays(&self) -> impl Iterator<Item = Duration> {
+        let initial_delay = Duration::from_secs(self.initial_delay_secs);
+        let max_delay = Duration::from_secs(self.max_delay_secs);
+        (0..self.retries).map(move |retry| {
+            initial_delay
+                .saturating_mul(2u32.saturating_pow(retry))
+                .min(max_delay)
+        })
+    }
+
+    /// Run `attempt` until it succeeds, fails with an error `is_transient` rejects, or runs out of
+    /// retries. The error of the last attempt is returned.
+    pub fn run<T, E: Display>(
+        &self,
+        description: &str,
+        mut attempt: impl FnMut() -> Result<T, E>,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let mut delays = self.delays();
+        loop {
+            match attempt() {
+                Err(e) if is_transient(&e) => match delays.next() {
+                    Some(delay) => {
+                        log::warn!(
+                            "{} failed: {}. Retrying in {}s...",
+                            description,
+                            e,
+                            delay.as_secs().as_important_value()
+                        );
+                        std::thread::sleep(delay);
+                    }
+                    None => return Err(e),
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether a request that failed this way could succeed if made again.
+fn is_transient(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::StatusCode(status) => matches!(status, 408 | 429 | 500..=599),
+        ureq::Error::Io(_)
+        | ureq::Error::Timeout(_)
+        | ureq::Error::HostNotFound
+        | ureq::Error::ConnectionFailed
+        | ureq::Error::Protocol(_)
+        | ureq::Error::BodyStalled
+        | ureq::Error::Decompress(_, _) => true,
+        _ => false,
+    }
+}
+
+/// Fetch a URL and parse its JSON body, retrying transient failures.
+pub fn get_json<T: DeserializeOwned>(retry: &RetryConfig, url: &str) -> Result<T, ureq::Error> {
+    retry.run(
+        &format!("Fetching {}", url),
+        || AGENT.get(url).call()?.into_body().read_json::<T>(),
+        is_transient,
+    )
+}
+
+/// Download a URL into a file, retrying transient failures. The file is emptied before each
+/// attempt.
+pub fn download(retry: &RetryConfig, url: &str, file: &mut File) -> Result<(), ureq::Error> {
+    retry.run(
+        &format!("Downloading {}", url),
+        || {
+            let response = AGENT.get(url).call()?;
+            file.set_len(0)?;
+            file.rewind()?;
+            std::io::copy(&mut response.into_body().into_reader(), file)?;
+            Ok(())
+        },
+        is_transient,
+    )
+}
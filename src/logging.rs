@@ -0,0 +1,190 @@
+use crate::{MojError, MojResult};
+use clap::ArgAction;
+use error_stack::ResultExt;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use owo_colors::{OwoColorize, Stream};
+use std::fs::File;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Whether progress bars are drawn, decided once the logger is set up.
+static SHOW_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default, clap::Args)]
+pub struct LogArgs {
+    /// Print more details. Give twice to also print the logs of libraries.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Print less. Give once to only print warnings and errors, twice to only print errors.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    quiet: u8,
+    /// Also append the logs to this file, with timestamps. Progress is logged there even when
+    /// `--quiet` is given.
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+}
+
+impl LogArgs {
+    fn level(&self) -> LevelFilter {
+        match i16::from(self.verbose) - i16::from(self.quiet) {
+            ..=-2 => LevelFilter::Error,
+            -1 => LevelFilter::Warn,
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            2.. => LevelFilter::Trace,
+        }
+    }
+}
+
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl Logger {
+    fn file_level(&self) -> LevelFilter {
+        if self.file.is_some() {
+            self.level.max(LevelFilter::Info)
+        } else {
+            LevelFilter::Off
+        }
+    }
+
+    /// Libraries only get to be as chatty as our own logs at the highest verbosity.
+    fn filter_for(&self, level: LevelFilter, target: &str) -> LevelFilter {
+        let own = target.starts_with(env!("CARGO_CRATE_NAME")) || target == GRADLE_TARGET;
+        if own || level == LevelFilter::Trace {
+            level
+        } else {
+            level.min(LevelFilter::Warn)
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter_for(self.level, metadata.target())
+            || metadata.level() <= self.filter_for(self.file_level(), metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        if record.level() <= self.filter_for(self.level, record.target()) {
+            match record.level() {
+                Level::Info => eprintln!("{}", message),
+                Level::Error => eprintln!(
+                    "{} {}",
+                    "Error:".if_supports_color(Stream::Stderr, |s| s.bright_red()),
+                    message
+                ),
+                Level::Warn => eprintln!(
+                    "{} {}",
+                    "Warning:".if_supports_color(Stream::Stderr, |s| s.bright_yellow()),
+                    message
+                ),
+                Level::Debug | Level::Trace => eprintln!(
+                    "{}",
+                    message.if_supports_color(Stream::Stderr, |s| s.dimmed())
+                ),
+            }
+        }
+        if record.level() <= self.filter_for(self.file_level(), record.target()) {
+            if let Some(file) = &self.file {
+                let line = format!(
+                    "{} {:<5} [{}] {}\n",
+                    chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                    record.level(),
+                    record.target(),
+                    strip_ansi(&message)
+                );
+                // There's nowhere left to report a failure to log
+                let _ = file.lock().unwrap().write_all(line.as_bytes());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// The target of the lines of Gradle output, so they can be told apart in the log file.
+pub const GRADLE_TARGET: &str = "gradle";
+
+/// Install the logger for the rest of the run. If the log file can't be opened, logging to stderr
+/// is still set up, so the error can be reported.
+pub fn init(args: &LogArgs) -> MojResult<()> {
+    let level = args.level();
+    SHOW_PROGRESS.store(
+        level >= LevelFilter::Info && std::io::stderr().is_terminal(),
+        Ordering::Relaxed,
+    );
+    let (file, result) = match args.log_file.as_deref().map(open_log_file).transpose() {
+        Ok(file) => (file, Ok(())),
+        Err(report) => (None, Err(report)),
+    };
+    let logger = Logger {
+        level,
+        file: file.map(Mutex::new),
+    };
+    log::set_max_level(logger.level.max(logger.file_level()));
+    log::set_boxed_logger(Box::new(logger)).expect("The logger is only set up once");
+    result
+}
+
+fn open_log_file(path: &Path) -> MojResult<File> {
+    File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .change_context(MojError::UserError)
+        .attach("Failed to open log file")
+        .attach_with(|| format!("Path: {:?}", path))
+}
+
+/// A progress bar counting up to `len`, drawn with `template`. It is hidden when stderr isn't a
+/// terminal or the output is quieted, so logs stay readable.
+pub fn progress_bar(len: u64, template: &str) -> ProgressBar {
+    hide_unless_interactive(
+        ProgressBar::new(len).with_style(ProgressStyle::default_bar().template(template).unwrap()),
+    )
+}
+
+/// A spinner for steps of unknown length, hidden like [progress_bar].
+pub fn spinner() -> ProgressBar {
+    hide_unless_interactive(ProgressBar::new_spinner())
+}
+
+fn hide_unless_interactive(bar: ProgressBar) -> ProgressBar {
+    if !SHOW_PROGRESS.load(Ordering::Relaxed) {
+        bar.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    bar
+}
+
+/// Remove the color codes from a message, for the log file.
+fn strip_ansi(message: &str) -> String {
+    let mut stripped = String::with_capacity(message.len());
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip the escape sequence up to and including its final letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
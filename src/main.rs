@@ -1,15 +1,46 @@
+mod change_report;
+mod class_artifacts;
+mod classfile;
 mod colorize;
 mod decompiler;
+mod decompiler_report;
+mod disassembler;
+mod export;
+mod highlight;
+mod html;
+mod http;
+mod logging;
+mod maintenance;
+mod member_history;
+mod rename_detection;
 mod repository;
+mod run_summary;
+mod search;
+mod server;
+mod site;
 
+use crate::change_report::ReportFormat;
 use crate::colorize::InfoColors;
-use crate::decompiler::{decompile_version, DecompileArtifact};
-use crate::repository::{MojRepository, SourcePath, TreeBase};
+use crate::decompiler::{decompile_version, DecompileArtifact, Toolchain};
+use crate::export::ExportFormat;
+use crate::http::RetryConfig;
+use crate::logging::LogArgs;
+use crate::member_history::HistoryFormat;
+use crate::rename_detection::RenameFormat;
+use crate::repository::{
+    CommitIdentity, MojRepository, Normalization, RepositoryOptions, SourcePath, TagConfig,
+    TreeBase,
+};
+use crate::run_summary::{RunSummary, SummaryFormat, VersionOutcome};
+use crate::search::SearchFormat;
 use chrono::{DateTime, Datelike, Utc};
-use error_stack::{Report, ResultExt};
+use clap::{Args, Parser, Subcommand};
+use error_stack::{AttachmentKind, FrameKind, Report, ResultExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -22,6 +53,8 @@ enum MojError {
     ParseConfig,
     #[error("Failed to fetch version manifest")]
     FetchVersionManifest,
+    #[error("Failed to fetch version details")]
+    FetchVersionDetails,
     #[error("Failed to open git repository")]
     OpenGitRepo,
     #[error("Failed to decompile version")]
@@ -32,36 +65,274 @@ enum MojError {
     Tag,
     #[error("Failed to reset repository")]
     Reset,
+    #[error("Failed to maintain repository")]
+    Maintenance,
+    #[error("Failed to export version")]
+    Export,
+    #[error("Failed to read version from repository")]
+    ReadVersion,
+    #[error("Failed to write change report")]
+    Report,
+    #[error("Failed to render site")]
+    Site,
+    #[error("Failed to serve repository")]
+    Serve,
+    #[error("Failed to search versions")]
+    Search,
+    #[error("Failed to follow member history")]
+    History,
+    #[error("Failed to write run summary")]
+    Summary,
+    #[error("Some versions failed to generate")]
+    VersionsFailed,
 }
 
 type MojResult<T> = Result<T, Report<MojError>>;
 
-fn main() -> MojResult<()> {
+/// A one-line description of an error, with its causes and attachments, for output that isn't a
+/// terminal.
+fn error_message(report: &Report<MojError>) -> String {
+    let mut parts = Vec::new();
+    for frame in report.frames() {
+        match frame.kind() {
+            FrameKind::Context(context) => parts.push(context.to_string()),
+            FrameKind::Attachment(AttachmentKind::Printable(attachment)) => {
+                parts.push(attachment.to_string())
+            }
+            FrameKind::Attachment(_) => {}
+        }
+    }
+    parts.join(": ")
+}
+
+const REPOSITORY_PATH: &str = "./repository";
+
+/// Open the existing repository, for the commands that don't create it.
+fn open_repository(config: &Config) -> MojResult<MojRepository> {
+    MojRepository::open(Path::new(REPOSITORY_PATH), config.repository_options())
+}
+
+/// A version id usable as a file name.
+fn version_file_name(version_id: &str) -> String {
+    version_id.replace(['/', '\\'], "_")
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    logging: LogArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Add and remove versions so the repository matches the config. This is the default.
+    Update(UpdateArgs),
+    /// Expire reflogs, prune unreachable objects and repack the repository.
+    Maintain {
+        /// Only prune unreachable objects this many days old or older, like `git gc --prune`.
+        #[arg(long, default_value_t = 14)]
+        prune_days: u64,
+    },
+    /// Write the files of a version out of the repository.
+    Export {
+        /// The version to export.
+        version: String,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Dir)]
+        format: ExportFormat,
+        /// Where to write the export. Defaults to `./exports/<version>`, plus the extension of the
+        /// format.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Only export this artifact, by its directory in the repository, e.g. `src`. Can be given
+        /// more than once.
+        #[arg(long = "artifact", value_parser = parse_artifact)]
+        artifacts: Vec<DecompileArtifact>,
+    },
+    /// Summarize the changes between two versions.
+    Report {
+        /// The older version.
+        from: String,
+        /// The newer version.
+        to: String,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+        /// The file to write the report to, instead of printing it.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Match the classes renamed or moved between two versions.
+    Renames {
+        /// The older version.
+        from: String,
+        /// The newer version.
+        to: String,
+        #[arg(long, value_enum, default_value_t = RenameFormat::Text)]
+        format: RenameFormat,
+        /// The file to write the rename map to, instead of printing it.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Render a static HTML site to browse the versions and their changes.
+    Site {
+        /// The directory to write the site to. Defaults to `./site`.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Serve a local HTTP API to query the versions, their files and their changes.
+    Serve {
+        /// The address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+    /// Search every version for a text or a symbol, and show when it was first seen, last seen and
+    /// changed.
+    Search {
+        /// The text to search for, or with `--symbol` part of a symbol such as
+        /// `LivingEntity#hurtServer`. Case is ignored.
+        query: String,
+        /// Search the symbols of the API signatures instead of the text of the files.
+        #[arg(long)]
+        symbol: bool,
+        #[arg(long, value_enum, default_value_t = SearchFormat::Text)]
+        format: SearchFormat,
+        /// The most symbols or files to show in text output.
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// Show every version in which the source or signature of a member changed, with the diff.
+    History {
+        /// The member, e.g. `net.minecraft.world.entity.LivingEntity#hurtServer`. Add parameter
+        /// types like `#hurtServer(int)` to pick one overload, and use `<init>` for constructors.
+        member: String,
+        #[arg(long, value_enum, default_value_t = HistoryFormat::Text)]
+        format: HistoryFormat,
+    },
+}
+
+#[derive(Debug, Default, Args)]
+struct UpdateArgs {
+    /// How to print the summary at the end of the run.
+    #[arg(long, value_enum, default_value_t)]
+    output: SummaryFormat,
+    /// Also write the summary as JSON to this file.
+    #[arg(long)]
+    summary_file: Option<PathBuf>,
+    /// Don't stop at a version that fails to generate. It is skipped or committed as a placeholder,
+    /// as configured by `failed_versions`, and the run fails once every other version is done.
+    #[arg(long)]
+    keep_going: bool,
+}
+
+fn parse_artifact(path: &str) -> Result<DecompileArtifact, String> {
+    DecompileArtifact::from_path_in_repository(path).ok_or_else(|| {
+        let paths: Vec<_> = DecompileArtifact::all()
+            .iter()
+            .map(|artifact| artifact.path_in_repository())
+            .collect();
+        format!("expected one of: {}", paths.join(", "))
+    })
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = logging::init(&cli.logging).and_then(|()| run(cli.command));
+    let code = match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(report) => {
+            log::error!("{:?}", report);
+            ExitCode::FAILURE
+        }
+    };
+    log::logger().flush();
+    code
+}
+
+fn run(command: Option<Command>) -> MojResult<()> {
     let config = Config::load()?;
-    let spinner = indicatif::ProgressBar::new_spinner();
+    match command.unwrap_or_else(|| Command::Update(UpdateArgs::default())) {
+        Command::Update(args) => update(&config, &args),
+        Command::Maintain { prune_days } => maintenance::maintain(&config, prune_days),
+        Command::Export {
+            version,
+            format,
+            output,
+            artifacts,
+        } => export::export(&config, &version, format, output, &artifacts),
+        Command::Report {
+            from,
+            to,
+            format,
+            output,
+        } => change_report::report(&config, &from, &to, format, output),
+        Command::Renames {
+            from,
+            to,
+            format,
+            output,
+        } => rename_detection::renames(&config, &from, &to, format, output),
+        Command::Site { output } => site::site(&config, output),
+        Command::Serve { address } => server::serve(&config, &address),
+        Command::Search {
+            query,
+            symbol,
+            format,
+            limit,
+        } => search::search(&config, &query, symbol, format, limit),
+        Command::History { member, format } => member_history::history(&config, &member, format),
+    }
+}
+
+/// Run an update, then report what it did, even if it failed.
+fn update(config: &Config, args: &UpdateArgs) -> MojResult<()> {
+    let mut summary = RunSummary::start();
+    let result =
+        update_versions(config, args, &mut summary).and_then(|()| match summary.failures.len() {
+            0 => Ok(()),
+            failed => Err(Report::new(MojError::VersionsFailed)
+                .attach(format!("{} versions failed, see the summary", failed))),
+        });
+    if let Err(report) = &result {
+        if summary.failures.is_empty() {
+            summary.add_failure(None, report);
+        }
+    }
+    summary.finish(result.is_ok());
+    if let Err(emit_report) = summary.emit(args.output, args.summary_file.as_deref()) {
+        if result.is_ok() {
+            return Err(emit_report);
+        }
+        // The error of the update matters more, so the summary's is only logged
+        log::error!("{:?}", emit_report);
+    }
+    result
+}
+
+fn update_versions(config: &Config, args: &UpdateArgs, summary: &mut RunSummary) -> MojResult<()> {
+    let spinner = logging::spinner();
     spinner.suspend(|| {
-        eprintln!(
+        log::info!(
             "Minimum version: {}",
             config.min_version.as_important_value()
         );
-        eprintln!(
+        log::info!(
             "Maximum version: {}",
             config.max_version.as_important_value()
         );
-        eprintln!(
+        log::info!(
             "Include snapshots: {}",
             config.include_snapshots.as_important_value()
         );
     });
     spinner.set_message("Fetching version manifest...");
-    let mut all_versions =
-        ureq::get("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json")
-            .call()
-            .change_context(MojError::FetchVersionManifest)?
-            .into_body()
-            .read_json::<VersionManifest>()
-            .change_context(MojError::FetchVersionManifest)?
-            .versions;
+    let mut all_versions = http::get_json::<VersionManifest>(
+        &config.retry,
+        "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json",
+    )
+    .change_context(MojError::FetchVersionManifest)?
+    .versions;
 
     let extracted_release_times = all_versions
         .iter()
@@ -75,7 +346,7 @@ fn main() -> MojResult<()> {
             }
         });
     let (min_release_time, max_release_time) =
-        verify_release_times(&config, extracted_release_times)?;
+        verify_release_times(config, extracted_release_times)?;
 
     spinner.set_message("Sorting versions...");
     all_versions.sort_by_key(|version| version.release_time);
@@ -91,16 +362,20 @@ fn main() -> MojResult<()> {
     });
 
     spinner.finish_and_clear();
-    eprintln!("Found {} versions", versions.len().as_important_value());
+    log::info!("Found {} versions", versions.len().as_important_value());
 
-    let repo_path = Path::new("./repository");
+    let repo_path = Path::new(REPOSITORY_PATH);
     let repo = if repo_path.exists() {
-        eprintln!("Opening repository...");
-        MojRepository::open(repo_path)?
+        log::info!("Opening repository...");
+        MojRepository::open(repo_path, config.repository_options())?
     } else {
-        eprintln!("Creating repository...");
+        log::info!("Creating repository...");
         std::fs::create_dir(repo_path).change_context(MojError::OpenGitRepo)?;
-        MojRepository::init(repo_path)?
+        MojRepository::init(
+            repo_path,
+            config.bare_repository,
+            config.repository_options(),
+        )?
     };
 
     let parchment_versions = decompiler::index_parchment_mc_versions(&all_versions);
@@ -114,32 +389,76 @@ fn main() -> MojResult<()> {
             ))
         })
         .collect();
+    let version_ids: HashSet<_> = versions.iter().map(|version| &version.id).collect();
+    summary.removed = repo
+        .branch_versions()?
+        .into_iter()
+        .filter(|id| !version_ids.contains(id))
+        .collect();
 
     // Now that we have all the trees, rewind the branch to initial state.
-    eprintln!("Clearing branch to rebuild...");
+    log::info!("Clearing branch to rebuild...");
     repo.clear_branch()?;
-    repo.clear_index_and_working_tree()?;
+    let checkout = config.checkout && !repo.is_bare();
+    if checkout {
+        repo.clear_index_and_working_tree()?;
+    }
 
-    let progress_bar = indicatif::ProgressBar::new(versions.len() as u64)
-        .with_style(indicatif::ProgressStyle::default_bar().template(
-            "Version progress: {bar:40.white/blue} {pos:.cyan}/{len:.cyan} (running {elapsed_precise}, ETA {eta})",
-        ).unwrap());
+    let progress_bar = logging::progress_bar(
+        versions.len() as u64,
+        "Version progress: {bar:40.white/blue} {pos:.cyan}/{len:.cyan} (running {elapsed_precise}, ETA {eta})",
+    );
 
     for version in &versions {
-        progress_bar.tick();
-        eprintln!(); // Force the progress bar to be printed to console permanently.
-        progress_bar.suspend(|| -> MojResult<()> {
-            eprintln!("Checking version {}...", version.id.as_important_value());
+        if !progress_bar.is_hidden() {
+            progress_bar.tick();
+            eprintln!(); // Force the progress bar to be printed to console permanently.
+        }
+        let started = Instant::now();
+        let result = progress_bar.suspend(|| -> MojResult<(VersionOutcome, SavedInfo, Vec<_>)> {
+            log::info!("Checking version {}...", version.id.as_important_value());
+            let toolchain = Toolchain::current(version, parchment_versions[&version.id]);
             let mut tree_base = None;
             let mut existing_info = SavedInfo::default();
+            let mut outcome = VersionOutcome::Added;
             if let Some((tree, info)) = versions_to_tree.get(&version.id) {
-                if info.is_current() {
-                    eprintln!(
+                outcome = VersionOutcome::Regenerated;
+                let toolchain_changed = config.regenerate_on_toolchain_change
+                    && info.toolchain.as_ref() != Some(&toolchain);
+                let normalization_changed =
+                    info.normalization.unwrap_or_default() != config.normalization;
+                let bytecode_changed = info.include_bytecode != config.include_bytecode;
+                if info.failure().is_some() {
+                    // A placeholder has no artifacts, nor the toolchain they were made with
+                    log::info!(
+                        "Version {} failed to generate before, retrying.",
+                        version.id.as_important_value()
+                    );
+                } else if info.is_current()
+                    && !toolchain_changed
+                    && !normalization_changed
+                    && !bytecode_changed
+                {
+                    log::info!(
                         "Version {} already processed.",
                         version.id.as_important_value()
                     );
-                    repo.commit_and_tag(version, info, tree)?;
-                    return Ok(());
+                    let mut info = info.clone();
+                    if info.java_version.is_none() {
+                        info.java_version = fetch_java_version(config, version);
+                    }
+                    repo.commit_and_tag(version, &info, tree)?;
+                    return Ok((VersionOutcome::Reused, info, Vec::new()));
+                } else if toolchain_changed {
+                    log::info!(
+                        "Toolchain changed for version {}, regenerating all artifacts.",
+                        version.id.as_important_value()
+                    );
+                } else if normalization_changed {
+                    log::info!(
+                        "Normalization changed for version {}, regenerating all artifacts.",
+                        version.id.as_important_value()
+                    );
                 } else {
                     tree_base = Some(TreeBase {
                         tree: *tree,
@@ -151,8 +470,12 @@ fn main() -> MojResult<()> {
 
             let mut artifacts_needed = Vec::new();
             for artifact in DecompileArtifact::all().iter().copied() {
-                if existing_info.get_artifact_version(artifact) < artifact.version() {
-                    eprintln!(
+                // Whether bytecode is included changes the class listings, and nothing else
+                let outdated = existing_info.get_artifact_version(artifact) < artifact.version()
+                    || (artifact == DecompileArtifact::ClassListings
+                        && existing_info.include_bytecode != config.include_bytecode);
+                if outdated {
+                    log::info!(
                         "Requesting {} for version {}.",
                         artifact.description().as_important_value(),
                         version.id.as_important_value()
@@ -164,12 +487,43 @@ fn main() -> MojResult<()> {
                 }
             }
 
-            let result =
-                decompile_version(version, parchment_versions[&version.id], &artifacts_needed)?;
-            eprintln!(
+            // Artifacts derived from a regenerated artifact must be regenerated along with it
+            for artifact in DecompileArtifact::all().iter().copied() {
+                let source_needed = artifact
+                    .derived_from()
+                    .is_some_and(|source| artifacts_needed.contains(&source));
+                if source_needed && !artifacts_needed.contains(&artifact) {
+                    artifacts_needed.push(artifact);
+                    if let Some(base) = tree_base.as_mut() {
+                        base.paths_to_include
+                            .retain(|path| path != artifact.path_in_repository());
+                    }
+                }
+            }
+
+            let result = decompile_version(
+                config,
+                version,
+                parchment_versions[&version.id],
+                &artifacts_needed,
+            )?;
+            log::info!(
                 "Decompiled version {}, adding to repository...",
                 version.id.as_important_value()
             );
+            if let Some(report) = result.report() {
+                log::info!(
+                    "Decompiler report for {}: {}",
+                    version.id.as_important_value(),
+                    report.summary()
+                );
+            }
+            // Carried over artifacts were made with the toolchain recorded for them, which is kept
+            // rather than claiming the current one made them
+            let toolchain = match &tree_base {
+                Some(base) if !base.paths_to_include.is_empty() => existing_info.toolchain.clone(),
+                _ => Some(toolchain),
+            };
             let tree = repo.create_tree(
                 tree_base,
                 &result
@@ -180,20 +534,65 @@ fn main() -> MojResult<()> {
                         repo_root: artifact.path_in_repository().to_string(),
                     })
                     .collect::<Vec<_>>(),
+                &config.normalization,
             )?;
-            repo.commit_and_tag(version, &SavedInfo::current(), &tree)?;
-            eprintln!("Committed and tagged {}", version.id.as_important_value());
-            // reset everything to the initial state again, since we don't care to preserve anything
-            // on disk
-            repo.clear_index_and_working_tree()?;
-            Ok(())
-        })?;
+            let java_version = existing_info
+                .java_version
+                .or_else(|| fetch_java_version(config, version));
+            let info = SavedInfo::current(
+                toolchain,
+                java_version,
+                config.normalization,
+                config.include_bytecode,
+            );
+            repo.commit_and_tag(version, &info, &tree)?;
+            log::info!("Committed and tagged {}", version.id.as_important_value());
+            Ok((outcome, info, artifacts_needed))
+        });
+        match result {
+            Ok((outcome, info, generated)) => {
+                summary.add_version(&version.id, outcome, started.elapsed(), &info, &generated)
+            }
+            Err(report) => {
+                summary.add_failure(Some(&version.id), &report);
+                if !args.keep_going {
+                    summary.head = repo.head_commit_id().map(|oid| oid.to_string());
+                    return Err(report);
+                }
+                progress_bar.suspend(|| -> MojResult<()> {
+                    log::error!(
+                        "Failed to generate version {}: {}",
+                        version.id.as_important_value(),
+                        error_message(&report)
+                    );
+                    match config.failed_versions {
+                        FailedVersionAction::Skip => {
+                            log::warn!("Skipping version {}", version.id.as_important_value());
+                        }
+                        FailedVersionAction::Placeholder => {
+                            let failure = error_message(&report);
+                            let tree = repo.create_placeholder_tree(&version.id, &failure)?;
+                            let info = SavedInfo::failed(failure);
+                            repo.commit_and_tag(version, &info, &tree)?;
+                            log::warn!(
+                                "Committed a placeholder for version {}",
+                                version.id.as_important_value()
+                            );
+                        }
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        summary.head = repo.head_commit_id().map(|oid| oid.to_string());
         progress_bar.inc(1);
     }
 
-    eprintln!("All versions added");
-    // check out the current HEAD again
-    repo.checkout_head()?;
+    log::info!("All versions added");
+    if checkout {
+        // check out the current HEAD again
+        repo.checkout_head()?;
+    }
 
     Ok(())
 }
@@ -245,6 +644,64 @@ pub struct Version {
     pub release_time: DateTime<Utc>,
     #[serde(rename = "type")]
     pub type_: String,
+    /// The URL of the version's details.
+    #[serde(default)]
+    pub url: String,
+}
+
+impl Version {
+    /// The URL of the official changelog article for this version, if it has one. Articles aren't
+    /// linked from anywhere machine-readable, so this is based on their naming scheme.
+    pub fn changelog_url(&self) -> Option<String> {
+        const ARTICLE_BASE: &str = "https://www.minecraft.net/en-us/article/";
+        let slug = self.id.to_lowercase().replace('.', "-");
+        let slug = if let Some((base, pre)) = slug.split_once("-pre") {
+            format!("minecraft-{}-pre-release-{}", base, pre)
+        } else if let Some((base, rc)) = slug.split_once("-rc") {
+            format!("minecraft-{}-release-candidate-{}", base, rc)
+        } else if self.type_ == "release" {
+            format!("minecraft-java-edition-{}", slug)
+        } else if slug.contains("-snapshot-") {
+            // Newer snapshots are named like `26.1-snapshot-1`
+            format!("minecraft-{}", slug)
+        } else if self.type_ == "snapshot" {
+            format!("minecraft-snapshot-{}", slug)
+        } else {
+            return None;
+        };
+        Some(format!("{}{}", ARTICLE_BASE, slug))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct VersionDetails {
+    #[serde(rename = "javaVersion")]
+    java_version: Option<JavaVersion>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JavaVersion {
+    #[serde(rename = "majorVersion")]
+    major_version: u32,
+}
+
+/// Fetch the Java version the game requires. This is only used for display, so failures are
+/// reported and otherwise ignored.
+fn fetch_java_version(config: &Config, version: &Version) -> Option<u32> {
+    let result = http::get_json::<VersionDetails>(&config.retry, &version.url)
+        .change_context(MojError::FetchVersionDetails)
+        .attach_with(|| format!("URL: {}", version.url));
+    match result {
+        Ok(details) => details.java_version.map(|java| java.major_version),
+        Err(e) => {
+            log::warn!(
+                "Could not fetch Java version of {}: {:?}",
+                version.id.as_important_value(),
+                e
+            );
+            None
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -253,9 +710,60 @@ struct Config {
     max_version: String,
     #[serde(default)]
     include_snapshots: bool,
+    #[serde(default)]
+    include_bytecode: bool,
+    #[serde(default)]
+    quiet_gradle: bool,
+    #[serde(default)]
+    regenerate_on_toolchain_change: bool,
+    author_name: Option<String>,
+    author_email: Option<String>,
+    #[serde(default)]
+    wall_clock_committer_time: bool,
+    #[serde(default)]
+    bare_repository: bool,
+    #[serde(default = "default_true")]
+    checkout: bool,
+    #[serde(default)]
+    stage_renames: bool,
+    #[serde(default)]
+    normalization: Normalization,
+    #[serde(default)]
+    tags: TagConfig,
+    #[serde(default)]
+    failed_versions: FailedVersionAction,
+    #[serde(default)]
+    retry: RetryConfig,
+}
+
+/// What `update --keep-going` does with a version that fails to generate.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FailedVersionAction {
+    /// Leave the version out of the branch.
+    #[default]
+    Skip,
+    /// Commit and tag the version with a tree that only explains the failure.
+    Placeholder,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl Config {
+    fn repository_options(&self) -> RepositoryOptions {
+        RepositoryOptions {
+            tags: self.tags.clone(),
+            identity: CommitIdentity {
+                name: self.author_name.clone(),
+                email: self.author_email.clone(),
+                wall_clock_committer_time: self.wall_clock_committer_time,
+            },
+            stage_renames: self.stage_renames,
+        }
+    }
+
     fn load() -> MojResult<Self> {
         let config_path = Path::new("./config.toml");
         let config = std::fs::read_to_string(config_path)
@@ -269,30 +777,106 @@ impl Config {
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SavedInfo {
+    /// The version of this format, or 0 for info written before it was versioned.
+    #[serde(default)]
+    info_version: u32,
     #[serde(default)]
     #[serde(alias = "output_version")]
     decompiled_classes_version: u32,
     #[serde(default)]
     libraries_output_version: u32,
+    #[serde(default)]
+    class_listings_version: u32,
+    #[serde(default)]
+    api_signatures_version: u32,
+    #[serde(default)]
+    class_index_version: u32,
+    #[serde(default)]
+    decompiler_report_version: u32,
+    /// The Java version the game requires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    java_version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mojankinator_version: Option<String>,
+    /// The toolchain that produced the artifacts. It stays that of the older artifacts when only
+    /// some are regenerated, and is missing if that is unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    toolchain: Option<Toolchain>,
+    /// The normalization applied to the committed files. Missing for versions committed before
+    /// normalization was configurable, which were not normalized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    normalization: Option<Normalization>,
+    /// Whether the class listings include the bytecode of methods.
+    #[serde(default)]
+    include_bytecode: bool,
+    /// Whether the classes the version moves were looked for and staged in a commit of their own,
+    /// whether or not any were found.
+    #[serde(default)]
+    renames_staged: bool,
+    /// Why generating the version failed, if it was committed as a placeholder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    failure: Option<String>,
 }
 
 impl SavedInfo {
-    pub fn current() -> Self {
+    const INFO_VERSION: u32 = 2;
+
+    pub fn current(
+        toolchain: Option<Toolchain>,
+        java_version: Option<u32>,
+        normalization: Normalization,
+        include_bytecode: bool,
+    ) -> Self {
         Self {
+            info_version: Self::INFO_VERSION,
             decompiled_classes_version: DecompileArtifact::DecompiledClasses.version(),
             libraries_output_version: DecompileArtifact::LibrariesTxt.version(),
+            class_listings_version: DecompileArtifact::ClassListings.version(),
+            api_signatures_version: DecompileArtifact::ApiSignatures.version(),
+            class_index_version: DecompileArtifact::ClassIndex.version(),
+            decompiler_report_version: DecompileArtifact::DecompilerReport.version(),
+            java_version,
+            mojankinator_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            toolchain,
+            normalization: Some(normalization),
+            include_bytecode,
+            renames_staged: false,
+            failure: None,
         }
     }
 
+    /// The info of a placeholder committed for a version that failed to generate. None of its
+    /// artifacts are current, so the next update generates them again.
+    pub fn failed(failure: String) -> Self {
+        Self {
+            info_version: Self::INFO_VERSION,
+            mojankinator_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            failure: Some(failure),
+            ..Self::default()
+        }
+    }
+
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+
     pub fn get_artifact_version(&self, artifact: DecompileArtifact) -> u32 {
         match artifact {
             DecompileArtifact::DecompiledClasses => self.decompiled_classes_version,
             DecompileArtifact::LibrariesTxt => self.libraries_output_version,
+            DecompileArtifact::ClassListings => self.class_listings_version,
+            DecompileArtifact::ApiSignatures => self.api_signatures_version,
+            DecompileArtifact::ClassIndex => self.class_index_version,
+            DecompileArtifact::DecompilerReport => self.decompiler_report_version,
         }
     }
 
     pub fn is_current(&self) -> bool {
         self.decompiled_classes_version >= DecompileArtifact::DecompiledClasses.version()
             && self.libraries_output_version >= DecompileArtifact::LibrariesTxt.version()
+            && self.class_listings_version >= DecompileArtifact::ClassListings.version()
+            && self.api_signatures_version >= DecompileArtifact::ApiSignatures.version()
+            && self.class_index_version >= DecompileArtifact::ClassIndex.version()
+            && self.decompiler_report_version >= DecompileArtifact::DecompilerReport.version()
     }
 }
@@ -0,0 +1,45 @@
+use crate::colorize::InfoColors;
+use crate::{open_repository, Config, MojResult};
+use indicatif::HumanBytes;
+use std::time::Duration;
+
+/// Shrink the repository: expire the reflogs of what every run rewrites, prune the notes and
+/// objects left behind by previous runs, and repack everything else into a single pack.
+/// Unreachable objects are only pruned once they are `prune_days` old.
+pub fn maintain(config: &Config, prune_days: u64) -> MojResult<()> {
+    log::info!("Opening repository...");
+    let repo = open_repository(config)?;
+    let size_before = repo.object_store_size()?;
+
+    log::info!("Expiring reflogs...");
+    let expired = repo.expire_reflogs()?;
+    log::info!("Expired {} reflog entries", expired.as_important_value());
+
+    log::info!("Pruning notes...");
+    let notes_removed = repo.prune_notes()?;
+    log::info!(
+        "Removed {} notes of unreachable commits",
+        notes_removed.as_important_value()
+    );
+
+    log::info!("Repacking...");
+    let stats = repo.repack(Duration::from_secs(prune_days * 24 * 60 * 60))?;
+    log::info!(
+        "Kept {} objects, pruned {} unreachable objects",
+        stats.objects_kept.as_important_value(),
+        stats.objects_pruned.as_important_value()
+    );
+    log::info!(
+        "Removed {} old packs and {} loose objects",
+        stats.packs_removed.as_important_value(),
+        stats.loose_removed.as_important_value()
+    );
+
+    let size_after = repo.object_store_size()?;
+    log::info!(
+        "Repository size: {} -> {}",
+        HumanBytes(size_before).as_important_value(),
+        HumanBytes(size_after).as_important_value()
+    );
+    Ok(())
+}
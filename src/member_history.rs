@@ -0,0 +1,686 @@
+use crate::class_artifacts::{api_signatures_path, parse_api_signatures};
+use crate::colorize::InfoColors;
+use crate::rename_detection::{detect_renames, source_path};
+use crate::{open_repository, Config, MojError, MojResult};
+use error_stack::{Report, ResultExt};
+use git2::Oid;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::ops::Range;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, clap::ValueEnum)]
+pub enum HistoryFormat {
+    Text,
+    Json,
+}
+
+/// A member to follow, parsed from e.g. `a.b.C$Inner#name(int,java.lang.String)`.
+struct MemberSpec {
+    /// The binary name of the class, e.g. `a.b.C$Inner`.
+    class: String,
+    /// The member name, or `<init>` for constructors.
+    name: String,
+    /// The simple names of the parameter types, if given to pick one overload.
+    params: Option<Vec<String>>,
+}
+
+impl MemberSpec {
+    fn parse(spec: &str) -> Option<Self> {
+        let (class, member) = spec.split_once('#')?;
+        if class.is_empty() || member.is_empty() {
+            return None;
+        }
+        let (name, params) = match member.split_once('(') {
+            Some((name, params)) => (name, Some(parse_params(params.strip_suffix(')')?))),
+            None => (member, None),
+        };
+        Some(MemberSpec {
+            class: class.to_string(),
+            name: name.to_string(),
+            params,
+        })
+    }
+
+    /// The simple name of the class, which is also the name of its constructors in source.
+    fn simple_class_name(&self) -> &str {
+        self.class.rsplit(['.', '$']).next().unwrap()
+    }
+
+    /// The member part of an API signature key of this member, e.g. `name(int)`.
+    fn member_key<'a>(&self, key: &'a str) -> Option<&'a str> {
+        let member = key.strip_prefix(&self.class)?.strip_prefix('#')?;
+        let matches = match member.split_once('(') {
+            Some((name, params)) => {
+                name == self.name
+                    && self.params.as_ref().is_none_or(|spec| {
+                        &parse_params(params.strip_suffix(')').unwrap_or(params)) == spec
+                    })
+            }
+            None => member == self.name && self.params.is_none(),
+        };
+        matches.then_some(member)
+    }
+
+    fn matches_declaration(&self, declaration: &Declaration) -> bool {
+        match declaration {
+            Declaration::Method { name, params } => {
+                (name == &self.name || self.name == "<init>" && name == self.simple_class_name())
+                    && self.params.as_ref().is_none_or(|spec| spec == params)
+            }
+            Declaration::Field(name) => name == &self.name && self.params.is_none(),
+            Declaration::Type { .. } | Declaration::Other => false,
+        }
+    }
+}
+
+fn parse_params(params: &str) -> Vec<String> {
+    params
+        .split(',')
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .map(simple_type_name)
+        .collect()
+}
+
+/// Reduce a type as written in source or in a signature key to its simple erased name, so the two
+/// can be compared, e.g. `java.util.List<String>` to `List` and `Outer.Inner...` to `Inner[]`.
+fn simple_type_name(type_name: &str) -> String {
+    let mut erased = String::new();
+    let mut depth = 0;
+    for c in type_name.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            _ if depth == 0 && !c.is_whitespace() => erased.push(c),
+            _ => {}
+        }
+    }
+    let erased = erased.replace("...", "[]");
+    erased.rsplit(['.', '$']).next().unwrap().to_string()
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|i| from + i)
+}
+
+/// Blank out comments and literals, keeping every byte offset, so that braces and parentheses in
+/// them don't get in the way of finding declarations.
+fn mask(source: &str) -> Vec<u8> {
+    let bytes = source.as_bytes();
+    let mut masked = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        let end = if rest.starts_with(b"//") {
+            find(bytes, i, b"\n").unwrap_or(bytes.len())
+        } else if rest.starts_with(b"/*") {
+            find(bytes, i + 2, b"*/").map_or(bytes.len(), |end| end + 2)
+        } else if rest.starts_with(b"\"\"\"") {
+            literal_end(bytes, i, b"\"\"\"")
+        } else if rest[0] == b'"' || rest[0] == b'\'' {
+            literal_end(bytes, i, &rest[..1])
+        } else {
+            i += 1;
+            continue;
+        };
+        for byte in &mut masked[i..end] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+        i = end;
+    }
+    masked
+}
+
+/// The end of the literal starting at `start`. Unterminated literals end at the end of the line,
+/// except text blocks.
+fn literal_end(bytes: &[u8], start: usize, quote: &[u8]) -> usize {
+    let mut i = start + quote.len();
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i..].starts_with(quote) {
+            return i + quote.len();
+        } else if quote.len() == 1 && bytes[i] == b'\n' {
+            return i;
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// Split the body of a class, or a whole file, into the ranges of its declarations.
+fn declarations(masked: &[u8], body: Range<usize>) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut push = |start: usize, end: usize| {
+        let start = (start..end)
+            .find(|&i| !masked[i].is_ascii_whitespace())
+            .unwrap_or(end);
+        if end - start > 1 {
+            ranges.push(start..end);
+        }
+    };
+    let mut start = body.start;
+    let mut parens = 0;
+    let mut braces = 0;
+    // A field initializer can contain blocks, like lambdas and anonymous classes, so only a
+    // semicolon ends it
+    let mut initializer = false;
+    for i in body {
+        match masked[i] {
+            b'(' => parens += 1,
+            b')' => parens -= 1,
+            b'{' => braces += 1,
+            b'}' => {
+                braces -= 1;
+                if braces == 0 && parens == 0 && !initializer {
+                    push(start, i + 1);
+                    start = i + 1;
+                }
+            }
+            b'=' if braces == 0 && parens == 0 => initializer = true,
+            b';' if braces == 0 && parens == 0 => {
+                push(start, i + 1);
+                start = i + 1;
+                initializer = false;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+enum Declaration {
+    Type { name: String, body: Range<usize> },
+    Method { name: String, params: Vec<String> },
+    Field(String),
+    Other,
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Remove annotations, which can contain parentheses, from a declaration header.
+fn strip_annotations(header: &str) -> String {
+    let mut stripped = String::with_capacity(header.len());
+    let mut chars = header.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '@' {
+            stripped.push(c);
+            continue;
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek().filter(|&&c| is_identifier_char(c) || c == '.') {
+            name.push(c);
+            chars.next();
+        }
+        if name == "interface" {
+            stripped.push_str(" interface");
+            continue;
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek() == Some(&'(') {
+            let mut depth = 0;
+            for c in chars.by_ref() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        stripped.push(' ');
+    }
+    stripped
+}
+
+fn classify(masked: &[u8], range: Range<usize>) -> Declaration {
+    // The header ends where the body or the initializer starts
+    let mut parens = 0;
+    let mut header_end = range.end;
+    for i in range.clone() {
+        match masked[i] {
+            b'(' => parens += 1,
+            b')' => parens -= 1,
+            b'{' | b'=' | b';' if parens == 0 => {
+                header_end = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let header = strip_annotations(&String::from_utf8_lossy(&masked[range.start..header_end]));
+    let words: Vec<&str> = header
+        .split(|c: char| !is_identifier_char(c))
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    if let Some(i) = words
+        .iter()
+        .position(|word| ["class", "interface", "enum", "record"].contains(word))
+    {
+        return match (words.get(i + 1), masked.get(header_end)) {
+            (Some(name), Some(b'{')) => Declaration::Type {
+                name: name.to_string(),
+                body: header_end + 1..range.end - 1,
+            },
+            _ => Declaration::Other,
+        };
+    }
+    if let Some(open) = header.find('(') {
+        let name = header[..open]
+            .trim_end()
+            .rsplit(|c: char| !is_identifier_char(c))
+            .next()
+            .unwrap_or_default();
+        let close = header.rfind(')').unwrap_or(header.len());
+        let mut params = Vec::new();
+        let mut depth = 0;
+        let mut param = String::new();
+        for c in header[open + 1..close.max(open + 1)].chars().chain([',']) {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                ',' if depth == 0 => {
+                    // Drop the parameter name and modifiers, keeping the type
+                    let mut tokens: Vec<&str> = param
+                        .split_whitespace()
+                        .filter(|token| *token != "final")
+                        .collect();
+                    tokens.pop();
+                    if !tokens.is_empty() {
+                        params.push(simple_type_name(&tokens.join(" ")));
+                    }
+                    param.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            param.push(c);
+        }
+        return Declaration::Method {
+            name: name.to_string(),
+            params,
+        };
+    }
+    match words.last() {
+        Some(name) if words.len() > 1 => Declaration::Field(name.to_string()),
+        _ => Declaration::Other,
+    }
+}
+
+/// Find the source of a member in the decompiled source of its outermost class. All overloads are
+/// included unless the spec gives parameter types.
+fn find_member_source(source: &str, spec: &MemberSpec) -> Option<String> {
+    let masked = mask(source);
+    let mut body = 0..source.len();
+    let outer = spec.class.split('$').next().unwrap();
+    let type_names =
+        std::iter::once(outer.rsplit('.').next().unwrap()).chain(spec.class.split('$').skip(1));
+    for type_name in type_names {
+        body = declarations(&masked, body).into_iter().find_map(|range| {
+            match classify(&masked, range) {
+                Declaration::Type { name, body } if name == type_name => Some(body),
+                _ => None,
+            }
+        })?;
+    }
+    let members: Vec<&str> = declarations(&masked, body)
+        .into_iter()
+        .filter(|range| spec.matches_declaration(&classify(&masked, range.clone())))
+        .map(|range| {
+            // Include the indentation of the first line
+            let line_start = source[..range.start].rfind('\n').map_or(0, |i| i + 1);
+            &source[line_start..range.end]
+        })
+        .collect();
+    (!members.is_empty()).then(|| members.join("\n\n") + "\n")
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberChangeKind {
+    Added,
+    Changed,
+    Removed,
+    /// Only the class of the member was renamed or moved.
+    Moved,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignatureChange {
+    /// The member part of the signature key, e.g. `hurtServer(int)`.
+    pub member: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// A version in which the source or signature of the member changed.
+#[derive(Debug, Serialize)]
+pub struct MemberChange {
+    pub version: String,
+    pub kind: MemberChangeKind,
+    /// The class the member is in after this version.
+    pub class: String,
+    /// The class the member was in before this version, if the class was renamed or moved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<String>,
+    pub signatures: Vec<SignatureChange>,
+    /// The unified diff of the member's source, without file headers.
+    pub diff: Vec<String>,
+}
+
+/// The member as of one version.
+#[derive(Default, PartialEq)]
+struct MemberState {
+    source: Option<String>,
+    /// Declarations from the API signatures, by the member part of their key.
+    signatures: BTreeMap<String, String>,
+}
+
+impl MemberState {
+    fn exists(&self) -> bool {
+        self.source.is_some() || !self.signatures.is_empty()
+    }
+}
+
+fn diff_sources(old: Option<&str>, new: Option<&str>) -> MojResult<Vec<String>> {
+    let old = old.unwrap_or_default();
+    let new = new.unwrap_or_default();
+    if old == new {
+        return Ok(Vec::new());
+    }
+    let patch = git2::Patch::from_buffers(
+        old.as_bytes(),
+        None,
+        new.as_bytes(),
+        None,
+        Some(git2::DiffOptions::new().context_lines(3)),
+    )
+    .change_context(MojError::History)
+    .attach("Failed to diff member sources")?;
+    let mut lines = Vec::new();
+    for h in 0..patch.num_hunks() {
+        let (hunk, count) = patch.hunk(h).change_context(MojError::History)?;
+        lines.push(
+            String::from_utf8_lossy(hunk.header())
+                .trim_end()
+                .to_string(),
+        );
+        for l in 0..count {
+            let line = patch.line_in_hunk(h, l).change_context(MojError::History)?;
+            let origin = line.origin();
+            if matches!(origin, '+' | '-' | ' ') {
+                let content = String::from_utf8_lossy(line.content());
+                lines.push(format!(
+                    "{}{}",
+                    origin,
+                    content.trim_end_matches(['\n', '\r'])
+                ));
+            }
+        }
+    }
+    Ok(lines)
+}
+
+fn signature_changes(old: &MemberState, new: &MemberState) -> Vec<SignatureChange> {
+    let members: std::collections::BTreeSet<&String> =
+        old.signatures.keys().chain(new.signatures.keys()).collect();
+    members
+        .into_iter()
+        .filter(|member| old.signatures.get(*member) != new.signatures.get(*member))
+        .map(|member| SignatureChange {
+            member: member.clone(),
+            from: old.signatures.get(member).cloned(),
+            to: new.signatures.get(member).cloned(),
+        })
+        .collect()
+}
+
+fn render_changes(spec: &str, changes: &[MemberChange]) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", spec).unwrap();
+    for change in changes {
+        let kind = match change.kind {
+            MemberChangeKind::Added => "added",
+            MemberChangeKind::Changed => "changed",
+            MemberChangeKind::Removed => "removed",
+            MemberChangeKind::Moved => "moved",
+        };
+        writeln!(out, "\n{}: {}", change.version, kind).unwrap();
+        if let Some(from) = &change.renamed_from {
+            writeln!(out, "  class {} -> {}", from, change.class).unwrap();
+        }
+        for signature in &change.signatures {
+            let from = signature.from.as_deref().unwrap_or("(none)");
+            let to = signature.to.as_deref().unwrap_or("(none)");
+            writeln!(out, "  signature {}: {} -> {}", signature.member, from, to).unwrap();
+        }
+        for line in &change.diff {
+            writeln!(out, "  {}", line).unwrap();
+        }
+    }
+    out
+}
+
+/// Show every version in which the source or the signature of a member changed, following its
+/// class through renames.
+pub fn history(config: &Config, member: &str, format: HistoryFormat) -> MojResult<()> {
+    let mut spec = MemberSpec::parse(member).ok_or_else(|| {
+        Report::new(MojError::UserError)
+            .attach(format!("{} is not a member", member))
+            .attach("Give it as package.Class#member, e.g. net.minecraft.world.entity.LivingEntity#hurtServer")
+    })?;
+    let repo = open_repository(config)?;
+    let mut versions = repo.list_versions()?;
+    // The placeholder of a failed version would look like the member was removed and added back
+    versions.retain(|version| !version.is_failed());
+    log::info!(
+        "Following {} through {} versions...",
+        member.as_important_value(),
+        versions.len().as_important_value()
+    );
+
+    let mut changes = Vec::new();
+    let mut state = MemberState::default();
+    let mut blobs: (Option<Oid>, Option<Oid>) = (None, None);
+    let mut previous_tree = None;
+    for version in &versions {
+        let mut renamed_from = None;
+        let outer = spec.class.split('$').next().unwrap().to_string();
+        let mut source_oid = repo.tree_file_oid(version.tree, &source_path(&outer))?;
+        if source_oid.is_none() && blobs.0.is_some() {
+            // The class is gone, but it may only have been renamed or moved
+            let renames = detect_renames(&repo, previous_tree.unwrap(), version.tree)?;
+            if let Some(rename) = renames.iter().find(|rename| rename.from == outer) {
+                renamed_from = Some(spec.class.clone());
+                spec.class = format!("{}{}", rename.to, &spec.class[outer.len()..]);
+                source_oid = repo.tree_file_oid(version.tree, &source_path(&rename.to))?;
+            }
+        }
+        previous_tree = Some(version.tree);
+        let api_oid = repo.tree_file_oid(version.tree, &api_signatures_path())?;
+        if (source_oid, api_oid) == blobs && renamed_from.is_none() {
+            continue;
+        }
+        blobs = (source_oid, api_oid);
+
+        let mut new_state = MemberState::default();
+        if let Some(oid) = source_oid {
+            let source = repo.read_blob(oid)?;
+            new_state.source = find_member_source(&String::from_utf8_lossy(&source), &spec);
+        }
+        if let Some(oid) = api_oid {
+            let api = repo.read_blob(oid)?;
+            new_state.signatures = parse_api_signatures(&String::from_utf8_lossy(&api))
+                .filter_map(|(key, declaration)| {
+                    Some((spec.member_key(key)?.to_string(), declaration.to_string()))
+                })
+                .collect();
+        }
+        if new_state == state && renamed_from.is_none() {
+            continue;
+        }
+        let kind = match (state.exists(), new_state.exists()) {
+            (false, false) => continue,
+            (false, true) => MemberChangeKind::Added,
+            (true, false) => MemberChangeKind::Removed,
+            (true, true) if new_state == state => MemberChangeKind::Moved,
+            (true, true) => MemberChangeKind::Changed,
+        };
+        changes.push(MemberChange {
+            version: version.id.clone(),
+            kind,
+            class: spec.class.clone(),
+            renamed_from,
+            signatures: signature_changes(&state, &new_state),
+            diff: diff_sources(state.source.as_deref(), new_state.source.as_deref())?,
+        });
+        state = new_state;
+    }
+
+    if changes.is_empty() {
+        return Err(Report::new(MojError::UserError)
+            .attach(format!("{} was not found in any version", member)));
+    }
+    log::info!(
+        "Found {} versions changing it",
+        changes.len().as_important_value()
+    );
+    match format {
+        HistoryFormat::Text => print!("{}", render_changes(member, &changes)),
+        HistoryFormat::Json => print!(
+            "{}",
+            serde_json::to_string_pretty(&changes)
+                .change_context(MojError::History)
+                .attach("Failed to serialize member history")?
+                + "\n"
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"package a.b;
+
+import java.util.List;
+
+public class C {
+    private int count = 1;
+
+    public C() {
+        this(0);
+    }
+
+    public C(int start) {
+        count = start;
+    }
+
+    // Braces in comments and strings don't count: run(int) {
+    public void run(int times) {
+        String brace = "}";
+    }
+
+    public void run(List<String> names, String... rest) {
+    }
+
+    static class Inner {
+        void run() {
+        }
+    }
+}
+"#;
+
+    fn source_of(spec: &str) -> Option<String> {
+        find_member_source(SOURCE, &MemberSpec::parse(spec).unwrap())
+    }
+
+    #[test]
+    fn parses_member_specs() {
+        let spec =
+            MemberSpec::parse("a.b.C$Inner#run(int, java.util.List<java.lang.String>)").unwrap();
+        assert_eq!(spec.class, "a.b.C$Inner");
+        assert_eq!(spec.name, "run");
+        assert_eq!(spec.params.as_deref().unwrap(), ["int", "List"]);
+        assert_eq!(spec.simple_class_name(), "Inner");
+
+        let spec = MemberSpec::parse("a.b.C#count").unwrap();
+        assert_eq!(spec.name, "count");
+        assert!(spec.params.is_none());
+        assert_eq!(
+            MemberSpec::parse("a.b.C#run()")
+                .unwrap()
+                .params
+                .unwrap()
+                .len(),
+            0
+        );
+
+        for invalid in ["a.b.C", "#run", "a.b.C#", "a.b.C#run(int"] {
+            assert!(MemberSpec::parse(invalid).is_none(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn matches_signature_keys() {
+        let spec = MemberSpec::parse("a.b.C#run(int)").unwrap();
+        assert_eq!(spec.member_key("a.b.C#run(int)"), Some("run(int)"));
+        assert_eq!(spec.member_key("a.b.C#run(long)"), None);
+        assert_eq!(spec.member_key("a.b.CD#run(int)"), None);
+        assert_eq!(spec.member_key("a.b.C$Inner#run(int)"), None);
+
+        let spec = MemberSpec::parse("a.b.C#run").unwrap();
+        assert_eq!(spec.member_key("a.b.C#run(long)"), Some("run(long)"));
+        assert_eq!(spec.member_key("a.b.C#runs()"), None);
+
+        let spec = MemberSpec::parse("a.b.C#count").unwrap();
+        assert_eq!(spec.member_key("a.b.C#count"), Some("count"));
+        let spec = MemberSpec::parse("a.b.C#count()").unwrap();
+        assert_eq!(spec.member_key("a.b.C#count"), None);
+    }
+
+    #[test]
+    fn finds_member_source() {
+        let run = source_of("a.b.C#run(int)").unwrap();
+        assert!(run.starts_with("    public void run(int times) {"));
+        assert!(run.contains("String brace = \"}\";"));
+        assert!(!run.contains("List"));
+
+        let overloads = source_of("a.b.C#run").unwrap();
+        assert!(overloads.contains("run(int times)"));
+        assert!(overloads.contains("run(List<String> names, String... rest)"));
+
+        let varargs = source_of("a.b.C#run(java.util.List,java.lang.String[])").unwrap();
+        assert!(varargs.contains("run(List<String> names, String... rest)"));
+        assert!(!varargs.contains("run(int times)"));
+
+        let constructor = source_of("a.b.C#<init>(int)").unwrap();
+        assert!(constructor.contains("public C(int start)"));
+        assert!(!constructor.contains("this(0)"));
+
+        assert!(source_of("a.b.C#count")
+            .unwrap()
+            .contains("private int count = 1;"));
+        assert!(source_of("a.b.C$Inner#run()")
+            .unwrap()
+            .contains("void run()"));
+        assert_eq!(source_of("a.b.C#missing"), None);
+        assert_eq!(source_of("a.b.C$Missing#run"), None);
+    }
+}
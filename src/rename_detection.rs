@@ -0,0 +1,497 @@
+use crate::colorize::InfoColors;
+use crate::decompiler::DecompileArtifact;
+use crate::repository::MojRepository;
+use crate::{open_repository, Config, MojError, MojResult};
+use error_stack::ResultExt;
+use git2::Oid;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+/// The minimum similarity for a removed and an added class to be considered the same class.
+const MIN_SIMILARITY: f64 = 0.5;
+/// Lines appearing in more added classes than this, such as `return null;`, say nothing about
+/// which class is which, so they aren't used to find candidates.
+const MAX_LINE_FREQUENCY: usize = 20;
+/// How many of the added classes sharing the most lines with a removed class are compared to it.
+const CANDIDATES_PER_CLASS: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassRename {
+    pub from: String,
+    pub to: String,
+    /// The combined similarity, from 0 to 1.
+    pub similarity: f64,
+    /// How similar the decompiled sources are, ignoring the package and imports.
+    pub source_similarity: f64,
+    /// How similar the class listings are, ignoring the packages of referenced classes. Missing if
+    /// either version has no class listings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structure_similarity: Option<f64>,
+}
+
+impl ClassRename {
+    /// The files in the repository that move along with the class, as `(from, to)` pairs: its
+    /// source, its listing, and the listings of its nested classes among `old_files`.
+    pub fn moved_paths<'a>(
+        &self,
+        old_files: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<(String, String)> {
+        let mut paths = vec![
+            (source_path(&self.from), source_path(&self.to)),
+            (listing_path(&self.from), listing_path(&self.to)),
+        ];
+        let from_nested = nested_listing_prefix(&self.from);
+        let to_nested = nested_listing_prefix(&self.to);
+        for path in old_files {
+            if let Some(nested) = path.strip_prefix(&from_nested) {
+                paths.push((path.to_string(), format!("{}{}", to_nested, nested)));
+            }
+        }
+        paths
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, clap::ValueEnum)]
+pub enum RenameFormat {
+    /// One `from -> to` line per class.
+    Text,
+    Json,
+}
+
+/// The class name of a decompiled source, e.g. `net.minecraft.Util` for `src/net/minecraft/Util.java`.
+pub fn class_name(path: &str) -> Option<String> {
+    let prefix = format!(
+        "{}/",
+        DecompileArtifact::DecompiledClasses.path_in_repository()
+    );
+    let name = path.strip_prefix(&prefix)?.strip_suffix(".java")?;
+    Some(name.replace('/', "."))
+}
+
+/// The path of the decompiled source of a class, e.g. `src/net/minecraft/Util.java`.
+pub fn source_path(class_name: &str) -> String {
+    format!(
+        "{}/{}.java",
+        DecompileArtifact::DecompiledClasses.path_in_repository(),
+        class_name.replace('.', "/")
+    )
+}
+
+fn listing_path(class_name: &str) -> String {
+    format!(
+        "{}/{}.txt",
+        DecompileArtifact::ClassListings.path_in_repository(),
+        class_name.replace('.', "/")
+    )
+}
+
+/// The start of the listing paths of the classes nested in a class, e.g.
+/// `classes/net/minecraft/Util$` for `net.minecraft.Util`.
+fn nested_listing_prefix(class_name: &str) -> String {
+    let listing = listing_path(class_name);
+    format!("{}$", listing.strip_suffix(".txt").unwrap())
+}
+
+fn simple_name(class_name: &str) -> &str {
+    class_name.rsplit('.').next().unwrap()
+}
+
+/// A multiset of hashed lines.
+type Fingerprint = HashMap<u64, u32>;
+
+fn fingerprint<'a>(lines: impl Iterator<Item = &'a str>) -> Fingerprint {
+    let mut fingerprint = Fingerprint::new();
+    for line in lines {
+        let mut hasher = DefaultHasher::new();
+        line.hash(&mut hasher);
+        *fingerprint.entry(hasher.finish()).or_default() += 1;
+    }
+    fingerprint
+}
+
+/// Fingerprint a decompiled source by its meaningful lines. The package and imports change when a
+/// class moves, and braces match everywhere.
+fn source_fingerprint(source: &str) -> Fingerprint {
+    fingerprint(
+        source
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.len() > 3)
+            .filter(|line| !line.starts_with("package ") && !line.starts_with("import ")),
+    )
+}
+
+/// Fingerprint a class listing by its structure: the declarations and bytecode, with referenced
+/// classes reduced to their simple names so that moving a package doesn't change them.
+fn structure_fingerprint(listing: &str) -> Fingerprint {
+    let normalized: Vec<String> = listing
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .map(|token| token.rsplit(['.', '/']).next().unwrap())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+    fingerprint(normalized.iter().map(String::as_str))
+}
+
+/// The weighted Jaccard similarity of two fingerprints.
+fn similarity(a: &Fingerprint, b: &Fingerprint) -> f64 {
+    let mut shared = 0;
+    let mut total = 0;
+    for (line, &count) in a {
+        let other = b.get(line).copied().unwrap_or(0);
+        shared += count.min(other);
+        total += count.max(other);
+    }
+    total += b
+        .iter()
+        .filter(|(line, _)| !a.contains_key(*line))
+        .map(|(_, &count)| count)
+        .sum::<u32>();
+    if total == 0 {
+        0.0
+    } else {
+        f64::from(shared) / f64::from(total)
+    }
+}
+
+struct Candidate {
+    class_name: String,
+    source: Fingerprint,
+    structure: Option<Fingerprint>,
+}
+
+fn read_candidates(repo: &MojRepository, tree: Oid, paths: &[String]) -> MojResult<Vec<Candidate>> {
+    let mut candidates = Vec::with_capacity(paths.len());
+    for path in paths {
+        let Some(class_name) = class_name(path) else {
+            continue;
+        };
+        let source = repo.read_tree_file(tree, path)?.unwrap_or_default();
+        let structure = repo
+            .read_tree_file(tree, &listing_path(&class_name))?
+            .map(|listing| structure_fingerprint(&String::from_utf8_lossy(&listing)));
+        candidates.push(Candidate {
+            class_name,
+            source: source_fingerprint(&String::from_utf8_lossy(&source)),
+            structure,
+        });
+    }
+    Ok(candidates)
+}
+
+/// Match the classes removed between two trees with the classes added, by the similarity of their
+/// sources and of their structure. Each class is matched at most once.
+pub fn detect_renames(repo: &MojRepository, old: Oid, new: Oid) -> MojResult<Vec<ClassRename>> {
+    let sources = DecompileArtifact::DecompiledClasses.path_in_repository();
+    let (removed, added) = repo.removed_and_added_files(old, new, sources)?;
+    if removed.is_empty() || added.is_empty() {
+        return Ok(Vec::new());
+    }
+    let removed = read_candidates(repo, old, &removed)?;
+    let added = read_candidates(repo, new, &added)?;
+
+    let mut line_index = HashMap::<u64, Vec<usize>>::new();
+    for (i, candidate) in added.iter().enumerate() {
+        for line in candidate.source.keys() {
+            line_index.entry(*line).or_default().push(i);
+        }
+    }
+    line_index.retain(|_, classes| classes.len() <= MAX_LINE_FREQUENCY);
+    let mut added_by_simple_name = HashMap::<&str, Vec<usize>>::new();
+    for (i, candidate) in added.iter().enumerate() {
+        added_by_simple_name
+            .entry(simple_name(&candidate.class_name))
+            .or_default()
+            .push(i);
+    }
+
+    let mut scored = Vec::new();
+    for (r, old_class) in removed.iter().enumerate() {
+        let mut shared_lines = HashMap::<usize, u32>::new();
+        for line in old_class.source.keys() {
+            for &a in line_index.get(line).into_iter().flatten() {
+                *shared_lines.entry(a).or_default() += 1;
+            }
+        }
+        let mut by_shared: Vec<_> = shared_lines.into_iter().collect();
+        by_shared.sort_by_key(|&(a, count)| (std::cmp::Reverse(count), a));
+        let mut compared: HashSet<usize> = by_shared
+            .into_iter()
+            .take(CANDIDATES_PER_CLASS)
+            .map(|(a, _)| a)
+            .collect();
+        // A class that kept its name when moving is always worth comparing
+        compared.extend(
+            added_by_simple_name
+                .get(simple_name(&old_class.class_name))
+                .into_iter()
+                .flatten(),
+        );
+
+        for a in compared {
+            let new_class = &added[a];
+            let source_similarity = similarity(&old_class.source, &new_class.source);
+            let structure_similarity = match (&old_class.structure, &new_class.structure) {
+                (Some(old), Some(new)) => Some(similarity(old, new)),
+                _ => None,
+            };
+            let combined = match structure_similarity {
+                Some(structure) => (source_similarity + structure) / 2.0,
+                None => source_similarity,
+            };
+            if combined >= MIN_SIMILARITY {
+                scored.push((combined, source_similarity, structure_similarity, r, a));
+            }
+        }
+    }
+
+    // Take the best matches first, so each class ends up with its most similar counterpart
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.3, a.4).cmp(&(b.3, b.4))));
+    let mut removed_matched = HashSet::new();
+    let mut added_matched = HashSet::new();
+    let mut renames = Vec::new();
+    for (combined, source_similarity, structure_similarity, r, a) in scored {
+        if removed_matched.contains(&r) || added_matched.contains(&a) {
+            continue;
+        }
+        removed_matched.insert(r);
+        added_matched.insert(a);
+        renames.push(ClassRename {
+            from: removed[r].class_name.clone(),
+            to: added[a].class_name.clone(),
+            similarity: combined,
+            source_similarity,
+            structure_similarity,
+        });
+    }
+    renames.sort_by(|a, b| a.from.cmp(&b.from));
+    Ok(renames)
+}
+
+pub fn render_renames(renames: &[ClassRename], format: RenameFormat) -> MojResult<String> {
+    match format {
+        RenameFormat::Text => {
+            let mut out = String::new();
+            for rename in renames {
+                writeln!(
+                    out,
+                    "{} -> {} ({:.0}%)",
+                    rename.from,
+                    rename.to,
+                    rename.similarity * 100.0
+                )
+                .unwrap();
+            }
+            Ok(out)
+        }
+        RenameFormat::Json => serde_json::to_string_pretty(renames)
+            .map(|json| json + "\n")
+            .change_context(MojError::Report)
+            .attach("Failed to serialize rename map"),
+    }
+}
+
+/// Detect the classes renamed or moved between two versions, and print the rename map or write it
+/// to a file.
+pub fn renames(
+    config: &Config,
+    from: &str,
+    to: &str,
+    format: RenameFormat,
+    output: Option<PathBuf>,
+) -> MojResult<()> {
+    let repo = open_repository(config)?;
+    log::info!(
+        "Matching classes of {} and {}...",
+        from.as_important_value(),
+        to.as_important_value()
+    );
+    let renames = detect_renames(&repo, repo.version_tree(from)?, repo.version_tree(to)?)?;
+    log::info!(
+        "Found {} renamed classes",
+        renames.len().as_important_value()
+    );
+    let rendered = render_renames(&renames, format)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)
+                .change_context(MojError::Report)
+                .attach_with(|| format!("Path: {:?}", path))?;
+            log::info!(
+                "Wrote rename map to {}",
+                path.display().as_important_value()
+            );
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{Normalization, RepositoryOptions, SourcePath};
+    use std::path::Path;
+
+    /// A class with a few distinct lines, so only its own copies are similar to it.
+    fn class_source(package: &str, name: &str, body: &str) -> String {
+        let mut source = format!("package {};\n\nimport java.util.List;\n\n", package);
+        writeln!(source, "public class {} {{", name).unwrap();
+        for i in 0..8 {
+            writeln!(source, "    int {}Field{} = {};", body, i, i * 7).unwrap();
+        }
+        source.push_str("}\n");
+        source
+    }
+
+    fn write_tree(repo: &MojRepository, dir: &Path, files: &[(&str, &str)]) -> Oid {
+        for (path, contents) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        let source = SourcePath {
+            root: dir.to_path_buf(),
+            repo_root: String::new(),
+        };
+        repo.create_tree(None, &[source], &Normalization::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn similarity_is_weighted_by_line_counts() {
+        let a = fingerprint(["one", "two", "two"].into_iter());
+        let b = fingerprint(["one", "two", "three"].into_iter());
+        assert_eq!(similarity(&a, &a), 1.0);
+        assert_eq!(similarity(&a, &b), 0.5);
+        assert_eq!(similarity(&a, &Fingerprint::new()), 0.0);
+        assert_eq!(similarity(&Fingerprint::new(), &Fingerprint::new()), 0.0);
+    }
+
+    #[test]
+    fn fingerprints_ignore_packages() {
+        let old = class_source("a.b", "Thing", "value");
+        let new = class_source("c.d", "Thing", "value");
+        assert_eq!(
+            similarity(&source_fingerprint(&old), &source_fingerprint(&new)),
+            1.0
+        );
+        let old = structure_fingerprint("class a.b.Thing\n  getfield a/b/Other.x");
+        let new = structure_fingerprint("class c.d.Thing\n  getfield c/d/Other.x");
+        assert_eq!(similarity(&old, &new), 1.0);
+    }
+
+    #[test]
+    fn detects_moved_classes_above_the_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo =
+            MojRepository::init(&dir.path().join("repo"), true, RepositoryOptions::default())
+                .unwrap();
+        let moved = class_source("a", "Moved", "moved");
+        let renamed = class_source("a", "Old", "renamed");
+        let old = write_tree(
+            &repo,
+            &dir.path().join("old"),
+            &[
+                ("src/a/Moved.java", &moved),
+                ("src/a/Old.java", &renamed),
+                ("src/a/Gone.java", &class_source("a", "Gone", "gone")),
+                ("src/a/Kept.java", &class_source("a", "Kept", "kept")),
+            ],
+        );
+        let new = write_tree(
+            &repo,
+            &dir.path().join("new"),
+            &[
+                (
+                    "src/b/Moved.java",
+                    &moved.replace("package a;", "package b;"),
+                ),
+                ("src/a/New.java", &renamed.replace("class Old", "class New")),
+                ("src/a/Added.java", &class_source("a", "Added", "added")),
+                ("src/a/Kept.java", &class_source("a", "Kept", "kept")),
+            ],
+        );
+        let renames = detect_renames(&repo, old, new).unwrap();
+        let pairs: Vec<_> = renames
+            .iter()
+            .map(|rename| (rename.from.as_str(), rename.to.as_str()))
+            .collect();
+        assert_eq!(pairs, [("a.Moved", "b.Moved"), ("a.Old", "a.New")]);
+        assert!(renames
+            .iter()
+            .all(|rename| rename.similarity >= MIN_SIMILARITY));
+        assert_eq!(renames[0].source_similarity, 1.0);
+        assert_eq!(renames[0].structure_similarity, None);
+    }
+
+    #[test]
+    fn matches_each_class_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo =
+            MojRepository::init(&dir.path().join("repo"), true, RepositoryOptions::default())
+                .unwrap();
+        let source = class_source("a", "Thing", "thing");
+        let old = write_tree(
+            &repo,
+            &dir.path().join("old"),
+            &[("src/a/Thing.java", &source)],
+        );
+        let new = write_tree(
+            &repo,
+            &dir.path().join("new"),
+            &[
+                (
+                    "src/b/Thing.java",
+                    &source.replace("package a;", "package b;"),
+                ),
+                (
+                    "src/c/Thing.java",
+                    &source.replace("package a;", "package c;"),
+                ),
+            ],
+        );
+        let renames = detect_renames(&repo, old, new).unwrap();
+        assert_eq!(renames.len(), 1);
+        assert_eq!(renames[0].from, "a.Thing");
+    }
+
+    #[test]
+    fn moves_nested_class_listings() {
+        let rename = ClassRename {
+            from: "a.Outer".to_string(),
+            to: "b.Renamed".to_string(),
+            similarity: 1.0,
+            source_similarity: 1.0,
+            structure_similarity: None,
+        };
+        let old_files = [
+            "classes/a/Outer.txt",
+            "classes/a/Outer$Inner.txt",
+            "classes/a/Outer$Inner$Deeper.txt",
+            "classes/a/OuterOther.txt",
+            "src/a/Outer.java",
+        ];
+        assert_eq!(
+            rename.moved_paths(old_files),
+            [
+                ("src/a/Outer.java", "src/b/Renamed.java"),
+                ("classes/a/Outer.txt", "classes/b/Renamed.txt"),
+                ("classes/a/Outer$Inner.txt", "classes/b/Renamed$Inner.txt"),
+                (
+                    "classes/a/Outer$Inner$Deeper.txt",
+                    "classes/b/Renamed$Inner$Deeper.txt"
+                ),
+            ]
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+        );
+    }
+}
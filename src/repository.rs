@@ -1,44 +1,585 @@
+use crate::colorize::InfoColors;
+use crate::decompiler::DecompileArtifact;
+use crate::rename_detection::detect_renames;
 use crate::{MojError, MojResult, SavedInfo, Version};
 use error_stack::{Report, ResultExt};
-use git2::{Index, IndexEntry, IndexTime, Oid, Repository, Signature, Time};
-use std::os::unix::ffi::OsStrExt;
-use std::os::unix::fs::MetadataExt;
+use git2::{Commit, FileMode, Oid, Repository, Signature, Time, Tree};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::io::Write as _;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 pub struct MojRepository {
     git_repo: Repository,
+    options: RepositoryOptions,
+}
+
+/// How versions are committed and tagged.
+#[derive(Debug, Default, Clone)]
+pub struct RepositoryOptions {
+    pub tags: TagConfig,
+    pub identity: CommitIdentity,
+    /// Before each version commit, commit the classes it moves on their own, so git's rename
+    /// detection can follow them.
+    pub stage_renames: bool,
+}
+
+/// Who version commits are attributed to. Missing parts are taken from the git config, and then
+/// from a built-in default identity.
+#[derive(Debug, Default, Clone)]
+pub struct CommitIdentity {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// Use the current time as the committer time, rather than the release time. The author time
+    /// is always the release time.
+    pub wall_clock_committer_time: bool,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagStyle {
+    #[default]
+    Annotated,
+    Lightweight,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TagConfig {
+    /// Prepended to the version id to make the tag name, e.g. `mc/`.
+    pub prefix: String,
+    pub style: TagStyle,
+    /// The message of annotated tags. `{version}`, `{type}` and `{release_date}` are replaced with
+    /// the details of the version.
+    pub message: String,
+    /// A command to sign annotated tags with. It receives the tag on stdin, and must print an
+    /// armored detached signature on stdout, e.g. `["gpg", "--batch", "-bsa"]`.
+    pub sign_command: Option<Vec<String>>,
+}
+
+impl Default for TagConfig {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            style: TagStyle::default(),
+            message: "{version}".to_string(),
+            sign_command: None,
+        }
+    }
 }
 
 impl MojRepository {
-    pub fn init(repo_path: &Path) -> MojResult<Self> {
-        let git_repo = Repository::init(repo_path).change_context(MojError::OpenGitRepo)?;
-        Ok(Self { git_repo })
+    pub fn init(repo_path: &Path, bare: bool, options: RepositoryOptions) -> MojResult<Self> {
+        let git_repo = if bare {
+            Repository::init_bare(repo_path)
+        } else {
+            Repository::init(repo_path)
+        }
+        .change_context(MojError::OpenGitRepo)?;
+        Ok(Self { git_repo, options })
     }
 
-    pub fn open(repo_path: &Path) -> MojResult<Self> {
+    pub fn open(repo_path: &Path, options: RepositoryOptions) -> MojResult<Self> {
         let git_repo = Repository::open(repo_path).change_context(MojError::OpenGitRepo)?;
-        Ok(Self { git_repo })
+        Ok(Self { git_repo, options })
+    }
+
+    const DEFAULT_NAME: &'static str = "Mojankinator";
+    const DEFAULT_EMAIL: &'static str = "mojankinator@localhost";
+
+    /// Resolve the name and email to commit with: the configured identity, then the git config,
+    /// then the built-in default.
+    fn identity(&self) -> (String, String) {
+        let git_config = self.git_repo.config().ok();
+        let from_git_config = |key: &str| {
+            git_config
+                .as_ref()
+                .and_then(|config| config.get_string(key).ok())
+                .filter(|value| !value.is_empty())
+        };
+        let name = self
+            .options
+            .identity
+            .name
+            .clone()
+            .or_else(|| from_git_config("user.name"))
+            .unwrap_or_else(|| Self::DEFAULT_NAME.to_string());
+        let email = self
+            .options
+            .identity
+            .email
+            .clone()
+            .or_else(|| from_git_config("user.email"))
+            .unwrap_or_else(|| Self::DEFAULT_EMAIL.to_string());
+        (name, email)
+    }
+
+    /// The notes ref that the [SavedInfo] of each version commit is stored under.
+    const INFO_NOTES_REF: &'static str = "refs/notes/mojankinator";
+
+    fn tag_name(&self, version_id: &str) -> String {
+        format!("{}{}", self.options.tags.prefix, version_id)
     }
 
-    fn version_reference(version_id: &str) -> String {
-        format!("refs/tags/{}", version_id)
+    fn version_reference(&self, version_id: &str) -> String {
+        format!("refs/tags/{}", self.tag_name(version_id))
     }
 
     /// Get the info of the commit tagged with the version id, if it exists.
     pub fn find_version_tree_and_info(&self, version_id: &str) -> Option<(Oid, SavedInfo)> {
         let commit = self
             .git_repo
-            .find_reference(&Self::version_reference(version_id))
+            .find_reference(&self.version_reference(version_id))
             .ok()?
             .peel_to_commit()
-            .expect("Tag should point to a commit");
-        let oid = commit.tree().expect("Commit should have a tree").id();
-        let message = commit.message().expect("Commit should have a message");
-        let saved_info = match message.split_once("\n\n") {
-            Some((_, info)) => toml::from_str(info).expect("Info should be deserializable"),
-            None => SavedInfo::default(),
+            .ok()?;
+        let oid = commit.tree().ok()?.id();
+        Some((oid, self.read_saved_info(version_id, &commit)))
+    }
+
+    const RENAME_LIMIT: usize = 50_000;
+
+    fn find_version_commit(&self, version_id: &str) -> MojResult<Commit<'_>> {
+        self.git_repo
+            .find_reference(&self.version_reference(version_id))
+            .and_then(|reference| reference.peel_to_commit())
+            .change_context(MojError::UserError)
+            .attach_with(|| format!("Version {} is not in the repository", version_id))
+    }
+
+    /// The release time of a version, which is the author time of its commit.
+    pub fn version_release_time(&self, version_id: &str) -> MojResult<i64> {
+        Ok(self
+            .find_version_commit(version_id)?
+            .author()
+            .when()
+            .seconds())
+    }
+
+    /// List the versions in the repository, oldest first, from the tags of their commits.
+    pub fn list_versions(&self) -> MojResult<Vec<VersionCommit>> {
+        let glob = format!("refs/tags/{}*", self.options.tags.prefix);
+        let mut versions = Vec::new();
+        for reference in self
+            .git_repo
+            .references_glob(&glob)
+            .change_context(MojError::ReadVersion)?
+        {
+            let reference = reference.change_context(MojError::ReadVersion)?;
+            let Some(id) = reference
+                .shorthand()
+                .and_then(|name| name.strip_prefix(&self.options.tags.prefix))
+                .map(str::to_string)
+            else {
+                continue;
+            };
+            let Ok(commit) = reference.peel_to_commit() else {
+                continue;
+            };
+            // Skip tags that happen to share the prefix
+            if commit.summary() != Some(&format!("Version {}", id)) {
+                continue;
+            }
+            versions.push(VersionCommit {
+                release_time: commit.author().when().seconds(),
+                commit: commit.id(),
+                tree: commit.tree_id(),
+                message: commit.message().unwrap_or_default().to_string(),
+                info: self.read_saved_info(&id, &commit),
+                id,
+            });
+        }
+        versions.sort_by(|a, b| (a.release_time, &a.id).cmp(&(b.release_time, &b.id)));
+        Ok(versions)
+    }
+
+    /// List every file of a tree with its blob.
+    pub fn tree_files(&self, tree: Oid) -> MojResult<Vec<(String, Oid)>> {
+        let tree = self
+            .git_repo
+            .find_tree(tree)
+            .change_context(MojError::ReadVersion)?;
+        let mut files = Vec::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                files.push((
+                    format!("{}{}", root, String::from_utf8_lossy(entry.name_bytes())),
+                    entry.id(),
+                ));
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .change_context(MojError::ReadVersion)?;
+        Ok(files)
+    }
+
+    pub fn read_blob(&self, oid: Oid) -> MojResult<Vec<u8>> {
+        Ok(self
+            .git_repo
+            .find_blob(oid)
+            .change_context(MojError::ReadVersion)
+            .attach_with(|| format!("Blob: {}", oid))?
+            .content()
+            .to_vec())
+    }
+
+    /// The tree of a version.
+    pub fn version_tree(&self, version_id: &str) -> MojResult<Oid> {
+        Ok(self.find_version_commit(version_id)?.tree_id())
+    }
+
+    /// Read a file of a version, if it exists.
+    pub fn read_version_file(&self, version_id: &str, path: &str) -> MojResult<Option<Vec<u8>>> {
+        self.read_tree_file(self.version_tree(version_id)?, path)
+    }
+
+    /// Read a file of a tree, if it exists.
+    pub fn read_tree_file(&self, tree: Oid, path: &str) -> MojResult<Option<Vec<u8>>> {
+        let Some(oid) = self.tree_file_oid(tree, path)? else {
+            return Ok(None);
+        };
+        let blob = self
+            .git_repo
+            .find_blob(oid)
+            .change_context(MojError::ReadVersion)
+            .attach_with(|| format!("Path: {}", path))?;
+        Ok(Some(blob.content().to_vec()))
+    }
+
+    /// The blob of a file of a tree, if it exists.
+    pub fn tree_file_oid(&self, tree: Oid, path: &str) -> MojResult<Option<Oid>> {
+        let tree = self
+            .git_repo
+            .find_tree(tree)
+            .change_context(MojError::ReadVersion)?;
+        Ok(tree
+            .get_path(Path::new(path))
+            .ok()
+            .filter(|entry| entry.kind() == Some(git2::ObjectType::Blob))
+            .map(|entry| entry.id()))
+    }
+
+    /// List the files under the given path that only exist in the old tree, and those that only
+    /// exist in the new tree.
+    pub fn removed_and_added_files(
+        &self,
+        old: Oid,
+        new: Oid,
+        path: &str,
+    ) -> MojResult<(Vec<String>, Vec<String>)> {
+        let old = self
+            .git_repo
+            .find_tree(old)
+            .change_context(MojError::ReadVersion)?;
+        let new = self
+            .git_repo
+            .find_tree(new)
+            .change_context(MojError::ReadVersion)?;
+        let diff = self
+            .git_repo
+            .diff_tree_to_tree(
+                Some(&old),
+                Some(&new),
+                Some(git2::DiffOptions::new().pathspec(path)),
+            )
+            .change_context(MojError::ReadVersion)
+            .attach("Cannot diff trees")?;
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for delta in diff.deltas() {
+            match delta.status() {
+                git2::Delta::Deleted => removed.extend(delta.old_file().path()),
+                git2::Delta::Added => added.extend(delta.new_file().path()),
+                _ => {}
+            }
+        }
+        let to_strings = |paths: Vec<&Path>| {
+            paths
+                .into_iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
         };
-        Some((oid, saved_info))
+        Ok((to_strings(removed), to_strings(added)))
+    }
+
+    /// Diff two trees, limited to a path if one is given, detecting renames.
+    fn diff_trees(&self, old: Oid, new: Oid, path: Option<&str>) -> MojResult<git2::Diff<'_>> {
+        let old = self
+            .git_repo
+            .find_tree(old)
+            .change_context(MojError::ReadVersion)?;
+        let new = self
+            .git_repo
+            .find_tree(new)
+            .change_context(MojError::ReadVersion)?;
+        let mut options = git2::DiffOptions::new();
+        if let Some(path) = path {
+            options.pathspec(path);
+        }
+        let mut diff = self
+            .git_repo
+            .diff_tree_to_tree(Some(&old), Some(&new), Some(&mut options))
+            .change_context(MojError::ReadVersion)
+            .attach("Cannot diff trees")?;
+        // Whole packages get moved at once, so allow far more candidates than git does by default
+        diff.find_similar(Some(
+            git2::DiffFindOptions::new()
+                .renames(true)
+                .rename_limit(Self::RENAME_LIMIT),
+        ))
+        .change_context(MojError::ReadVersion)
+        .attach("Cannot detect renames")?;
+        Ok(diff)
+    }
+
+    /// List the files that changed under the given path between two versions, detecting renames.
+    pub fn diff_versions(&self, from: &str, to: &str, path: &str) -> MojResult<Vec<FileChange>> {
+        let diff = self.diff_trees(self.version_tree(from)?, self.version_tree(to)?, Some(path))?;
+        let mut changes = Vec::with_capacity(diff.deltas().len());
+        for i in 0..diff.deltas().len() {
+            let patch = git2::Patch::from_diff(&diff, i).change_context(MojError::ReadVersion)?;
+            let (_, insertions, deletions) = patch
+                .map(|patch| patch.line_stats())
+                .transpose()
+                .change_context(MojError::ReadVersion)?
+                .unwrap_or_default();
+            changes.push(FileChange::new(
+                &diff.get_delta(i).unwrap(),
+                insertions,
+                deletions,
+            ));
+        }
+        Ok(changes)
+    }
+
+    /// Call `visit` with every file that changed between two trees, limited to a path if one is
+    /// given, along with its changed lines.
+    pub fn for_each_file_diff(
+        &self,
+        old: Oid,
+        new: Oid,
+        path: Option<&str>,
+        mut visit: impl FnMut(FileDiff) -> MojResult<()>,
+    ) -> MojResult<()> {
+        let diff = self.diff_trees(old, new, path)?;
+        for i in 0..diff.deltas().len() {
+            let delta = diff.get_delta(i).unwrap();
+            let patch = git2::Patch::from_diff(&diff, i).change_context(MojError::ReadVersion)?;
+            let mut hunks = Vec::new();
+            let mut binary = delta.flags().is_binary();
+            match &patch {
+                Some(patch) if !binary => {
+                    for h in 0..patch.num_hunks() {
+                        let lines = patch
+                            .num_lines_in_hunk(h)
+                            .change_context(MojError::ReadVersion)?;
+                        let mut hunk = Vec::with_capacity(lines);
+                        for l in 0..lines {
+                            let line = patch
+                                .line_in_hunk(h, l)
+                                .change_context(MojError::ReadVersion)?;
+                            let kind = match line.origin() {
+                                '+' => DiffLineKind::Added,
+                                '-' => DiffLineKind::Removed,
+                                ' ' => DiffLineKind::Context,
+                                // Markers such as "no newline at end of file"
+                                _ => continue,
+                            };
+                            hunk.push(DiffLine {
+                                kind,
+                                old_line: line.old_lineno(),
+                                new_line: line.new_lineno(),
+                                content: String::from_utf8_lossy(line.content())
+                                    .trim_end_matches(['\n', '\r'])
+                                    .to_string(),
+                            });
+                        }
+                        hunks.push(hunk);
+                    }
+                }
+                _ => binary = true,
+            }
+            let (insertions, deletions) =
+                hunks
+                    .iter()
+                    .flatten()
+                    .fold((0, 0), |(i, d), line| match line.kind {
+                        DiffLineKind::Added => (i + 1, d),
+                        DiffLineKind::Removed => (i, d + 1),
+                        DiffLineKind::Context => (i, d),
+                    });
+            visit(FileDiff {
+                change: FileChange::new(&delta, insertions, deletions),
+                binary,
+                hunks,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Call `visit` with the path, mode and contents of every file of a version. If any paths are
+    /// given, only the files under them are visited.
+    pub fn for_each_version_file(
+        &self,
+        version_id: &str,
+        paths: &[&str],
+        mut visit: impl FnMut(&str, i32, &[u8]) -> MojResult<()>,
+    ) -> MojResult<()> {
+        let tree = self
+            .find_version_commit(version_id)?
+            .tree()
+            .change_context(MojError::ReadVersion)?;
+        if paths.is_empty() {
+            return self.visit_tree(&tree, "", &mut visit);
+        }
+        for path in paths {
+            let Ok(entry) = tree.get_path(Path::new(path)) else {
+                log::warn!(
+                    "Version {} has no {}, skipping it",
+                    version_id.as_important_value(),
+                    path.as_important_value()
+                );
+                continue;
+            };
+            let object = entry
+                .to_object(&self.git_repo)
+                .change_context(MojError::ReadVersion)?;
+            match object.as_tree() {
+                Some(subtree) => self.visit_tree(subtree, path, &mut visit)?,
+                None => self.visit_blob(path, entry.filemode(), entry.id(), &mut visit)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_tree(
+        &self,
+        tree: &Tree,
+        prefix: &str,
+        visit: &mut impl FnMut(&str, i32, &[u8]) -> MojResult<()>,
+    ) -> MojResult<()> {
+        for entry in tree.iter() {
+            let name = entry.name().expect("tree entry names should be UTF-8");
+            let path = if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            match entry.kind() {
+                Some(git2::ObjectType::Tree) => {
+                    let subtree = self
+                        .git_repo
+                        .find_tree(entry.id())
+                        .change_context(MojError::ReadVersion)
+                        .attach_with(|| format!("Path: {}", path))?;
+                    self.visit_tree(&subtree, &path, visit)?;
+                }
+                Some(git2::ObjectType::Blob) => {
+                    self.visit_blob(&path, entry.filemode(), entry.id(), visit)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_blob(
+        &self,
+        path: &str,
+        mode: i32,
+        oid: Oid,
+        visit: &mut impl FnMut(&str, i32, &[u8]) -> MojResult<()>,
+    ) -> MojResult<()> {
+        let blob = self
+            .git_repo
+            .find_blob(oid)
+            .change_context(MojError::ReadVersion)
+            .attach_with(|| format!("Path: {}", path))?;
+        visit(path, mode, blob.content())
+    }
+
+    /// Read the info of a version commit. Info that can't be read is treated as missing, so the
+    /// version will be regenerated.
+    fn read_saved_info(&self, version_id: &str, commit: &git2::Commit) -> SavedInfo {
+        if let Ok(note) = self
+            .git_repo
+            .find_note(Some(Self::INFO_NOTES_REF), commit.id())
+        {
+            return match note.message().map(toml::from_str) {
+                Some(Ok(info)) => info,
+                _ => {
+                    log::warn!(
+                        "Ignoring unreadable info note for version {}",
+                        version_id.as_important_value()
+                    );
+                    SavedInfo::default()
+                }
+            };
+        }
+        // Older versions of mojankinator stored the info as TOML in the commit message body
+        commit
+            .message()
+            .and_then(|message| message.split_once("\n\n"))
+            .and_then(|(_, info)| toml::from_str(info).ok())
+            .unwrap_or_default()
+    }
+
+    /// Store the info of a version commit in its note, unless it is already there.
+    fn write_saved_info(
+        &self,
+        signature: &Signature,
+        commit: Oid,
+        saved_info: &SavedInfo,
+    ) -> MojResult<()> {
+        let info = toml::to_string(saved_info)
+            .change_context(MojError::Commit)
+            .attach("Failed to serialize commit info")?;
+        let existing = self
+            .git_repo
+            .find_note(Some(Self::INFO_NOTES_REF), commit)
+            .ok();
+        if existing.as_ref().and_then(|note| note.message()) == Some(info.as_str()) {
+            return Ok(());
+        }
+        self.git_repo
+            .note(
+                signature,
+                signature,
+                Some(Self::INFO_NOTES_REF),
+                commit,
+                &info,
+                true,
+            )
+            .change_context(MojError::Commit)
+            .attach("Failed to write commit info note")?;
+        Ok(())
+    }
+
+    /// The ids of the versions committed on the branch, oldest first.
+    pub fn branch_versions(&self) -> MojResult<Vec<String>> {
+        let mut commit = match self.git_repo.head() {
+            Ok(head) => head
+                .peel_to_commit()
+                .change_context(MojError::ReadVersion)?,
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => return Ok(Vec::new()),
+            Err(e) => return Err(e).change_context(MojError::ReadVersion),
+        };
+        let mut ids = Vec::new();
+        loop {
+            // Staged renames are skipped, as they aren't versions of their own
+            if let Some(id) = commit.summary().and_then(|s| s.strip_prefix("Version ")) {
+                ids.push(id.to_string());
+            }
+            match commit.parents().next() {
+                Some(parent) => commit = parent,
+                None => break,
+            }
+        }
+        ids.reverse();
+        Ok(ids)
     }
 
     pub fn clear_branch(&self) -> MojResult<()> {
@@ -72,17 +613,16 @@ impl MojRepository {
         Ok(())
     }
 
+    /// Write the files to the object database and build a tree out of them, on top of the
+    /// included paths of the base tree. Neither the repository's index nor its working tree (if
+    /// any) is touched.
     pub fn create_tree(
         &self,
         base: Option<TreeBase>,
         source_files: &[SourcePath],
+        normalization: &Normalization,
     ) -> MojResult<Oid> {
-        let mut index = self.git_repo.index().change_context(MojError::Commit)?;
-
-        index
-            .clear()
-            .change_context(MojError::Commit)
-            .attach("Cannot clear index")?;
+        let mut root = TreeNode::default();
 
         if let Some(base) = base {
             let base_tree = self
@@ -90,43 +630,82 @@ impl MojRepository {
                 .find_tree(base.tree)
                 .change_context(MojError::Commit)
                 .attach("Cannot find base tree")?;
-            index
-                .read_tree(&base_tree)
-                .change_context(MojError::Commit)?;
-            let mut pathspecs = Vec::with_capacity(1 + base.paths_to_include.len());
-            // Don't match the paths to include
             for path in &base.paths_to_include {
-                pathspecs.push(format!("!{}", path));
+                // A path that doesn't exist in the base tree has nothing to include
+                if let Ok(entry) = base_tree.get_path(Path::new(path)) {
+                    root.insert(path, entry.id(), entry.filemode());
+                }
             }
-            // Include all other paths
-            pathspecs.push("*".to_string());
-            index
-                .remove_all(pathspecs, None)
-                .change_context(MojError::Commit)
-                .attach("Cannot remove paths from index")?;
         }
 
+        let mut files = Vec::new();
         for SourcePath { root, repo_root } in source_files {
             if root.is_file() {
-                add_file_to_index(&mut index, root.parent().unwrap(), repo_root.as_str(), root)?;
-            } else {
-                for entry in walkdir::WalkDir::new(root) {
-                    let entry = entry.change_context(MojError::Commit)?;
-                    if entry.file_type().is_file() {
-                        add_file_to_index(&mut index, root, repo_root.as_str(), entry.path())?;
-                    } else if entry.file_type().is_dir() {
-                        // Skip directories
-                    } else {
-                        return Err(Report::new(MojError::Commit)
-                            .attach("Unknown file type, cannot copy")
-                            .attach(format!("File type: {:?}", entry.file_type()))
-                            .attach(format!("Path: {:?}", entry.path())));
-                    }
+                let path = Path::new(repo_root).join(root.file_name().unwrap());
+                files.push((path.to_string_lossy().into_owned(), root.clone()));
+                continue;
+            }
+            for entry in walkdir::WalkDir::new(root) {
+                let entry = entry.change_context(MojError::Commit)?;
+                if entry.file_type().is_file() {
+                    let relative = entry.path().strip_prefix(root).unwrap();
+                    let path = Path::new(repo_root).join(relative);
+                    files.push((path.to_string_lossy().into_owned(), entry.into_path()));
+                } else if entry.file_type().is_dir() {
+                    // Skip directories
+                } else {
+                    return Err(Report::new(MojError::Commit)
+                        .attach("Unknown file type, cannot copy")
+                        .attach(format!("File type: {:?}", entry.file_type()))
+                        .attach(format!("Path: {:?}", entry.path())));
                 }
             }
         }
 
-        index.write_tree().change_context(MojError::Commit)
+        // Repository handles can't be shared between threads, so each thread opens its own
+        let repo_path = self.git_repo.path();
+        let blobs = files
+            .par_iter()
+            .map_init(
+                || Repository::open(repo_path),
+                |repo, (_, file)| {
+                    let repo = repo.as_ref().map_err(|e| {
+                        Report::new(MojError::Commit)
+                            .attach("Cannot open repository to write blobs")
+                            .attach(e.message().to_string())
+                    })?;
+                    write_blob(repo, file, normalization)
+                },
+            )
+            .collect::<MojResult<Vec<_>>>()?;
+        for ((path, _), (oid, mode)) in files.iter().zip(blobs) {
+            root.insert(path, oid, mode);
+        }
+
+        root.write(&self.git_repo)
+            .change_context(MojError::Commit)
+            .attach("Cannot write tree")
+    }
+
+    /// The file explaining the failure in the tree of a placeholder version.
+    pub const PLACEHOLDER_FILE: &'static str = "FAILED.txt";
+
+    /// Build the tree committed in place of a version that failed to generate, which only holds
+    /// a file explaining the failure.
+    pub fn create_placeholder_tree(&self, version_id: &str, failure: &str) -> MojResult<Oid> {
+        let contents = format!(
+            "Version {} failed to generate, so this commit has none of its files.\n\n{}\n",
+            version_id, failure
+        );
+        let blob = self
+            .git_repo
+            .blob(contents.as_bytes())
+            .change_context(MojError::Commit)?;
+        let mut root = TreeNode::default();
+        root.insert(Self::PLACEHOLDER_FILE, blob, FileMode::Blob.into());
+        root.write(&self.git_repo)
+            .change_context(MojError::Commit)
+            .attach("Cannot write placeholder tree")
     }
 
     pub fn commit_and_tag(
@@ -135,55 +714,883 @@ impl MojRepository {
         saved_info: &SavedInfo,
         tree: &Oid,
     ) -> MojResult<()> {
-        let author = &self
-            .git_repo
-            .signature()
-            .change_context(MojError::Commit)
-            .attach("Cannot find user to commit with")?;
-        // Correct the signature with the release time
+        let (name, email) = self.identity();
         let author = Signature::new(
-            author.name().unwrap(),
-            author.email().unwrap(),
+            &name,
+            &email,
             &Time::new(version.release_time.timestamp(), 0),
         )
-        .unwrap();
-        let parent = match self.git_repo.head() {
+        .change_context(MojError::Commit)
+        .attach("Invalid commit author")
+        .attach_with(|| format!("Author: {} <{}>", name, email))?;
+        let committer = if self.options.identity.wall_clock_committer_time {
+            Signature::now(&name, &email).change_context(MojError::Commit)?
+        } else {
+            author.clone()
+        };
+        let mut parent = match self.git_repo.head() {
             Ok(head) => Some(head.peel_to_commit().unwrap()),
             Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
             Err(e) => return Err(e).change_context(MojError::Commit),
         };
 
+        let tree = self.git_repo.find_tree(*tree).unwrap();
+        let message = match self.reusable_message(version, saved_info, &tree, parent.as_ref()) {
+            Some(message) => message,
+            None => self.version_commit_message(version, saved_info, &tree, parent.as_ref())?,
+        };
+        let mut saved_info = saved_info.clone();
+        saved_info.renames_staged = self.options.stage_renames && parent.is_some();
+        if let Some(previous) = parent.as_ref().filter(|_| self.options.stage_renames) {
+            if let Some(staged) =
+                self.commit_staged_renames(version, previous, &tree, &author, &committer)?
+            {
+                parent = Some(staged);
+            }
+        }
+
         let commit = self
             .git_repo
             .commit(
                 Some("HEAD"),
                 &author,
-                &author,
-                &format!(
-                    "Version {}\n\n{}",
-                    version.id,
-                    toml::to_string(saved_info)
-                        .change_context(MojError::Commit)
-                        .attach("Failed to serialize commit info")?
-                ),
-                &self.git_repo.find_tree(*tree).unwrap(),
+                &committer,
+                &message,
+                &tree,
                 parent.as_ref().as_slice(),
             )
             .change_context(MojError::Commit)?;
+        self.write_saved_info(&committer, commit, &saved_info)?;
+        self.tag(version, commit, &committer)
+    }
 
-        self.git_repo
-            .tag(
-                &version.id,
-                &self.git_repo.find_commit(commit).unwrap().into_object(),
-                &author,
-                &version.id,
-                true,
+    /// The summary prefix of the commits made by [Self::commit_staged_renames].
+    const STAGED_RENAMES_PREFIX: &'static str = "Move classes for version ";
+
+    /// Commit the classes that a version moves on their own, with their old contents, so git's
+    /// rename detection sees the moves as exact renames. Returns the commit, if anything moved.
+    fn commit_staged_renames(
+        &self,
+        version: &Version,
+        previous: &Commit,
+        tree: &Tree,
+        author: &Signature,
+        committer: &Signature,
+    ) -> MojResult<Option<Commit<'_>>> {
+        let (staged_tree, message) = match self.reusable_staged_renames(version, previous, tree) {
+            Some(Some((staged_tree, message))) => (staged_tree, message),
+            Some(None) => return Ok(None),
+            None => match self.stage_renames(version, previous, tree)? {
+                Some(staged) => staged,
+                None => return Ok(None),
+            },
+        };
+        let staged_tree = self
+            .git_repo
+            .find_tree(staged_tree)
+            .change_context(MojError::Commit)?;
+        let commit = self
+            .git_repo
+            .commit(
+                Some("HEAD"),
+                author,
+                committer,
+                &message,
+                &staged_tree,
+                &[previous],
             )
+            .and_then(|oid| self.git_repo.find_commit(oid))
+            .change_context(MojError::Commit)?;
+        Ok(Some(commit))
+    }
+
+    /// Detect the classes a version moves, and write the previous tree with them moved. Returns
+    /// the tree and the message of its commit, if anything moved.
+    fn stage_renames(
+        &self,
+        version: &Version,
+        previous: &Commit,
+        tree: &Tree,
+    ) -> MojResult<Option<(Oid, String)>> {
+        let renames = detect_renames(self, previous.tree_id(), tree.id())?;
+        if renames.is_empty() {
+            return Ok(None);
+        }
+        let previous_tree = previous.tree().change_context(MojError::Commit)?;
+        let previous_files = self.tree_files(previous.tree_id())?;
+        let mut update = git2::build::TreeUpdateBuilder::new();
+        let mut message = format!("{}{}\n\n", Self::STAGED_RENAMES_PREFIX, version.id);
+        for rename in &renames {
+            writeln!(message, "{} -> {}", rename.from, rename.to).unwrap();
+            for (from, to) in
+                rename.moved_paths(previous_files.iter().map(|(path, _)| path.as_str()))
+            {
+                if let Ok(entry) = previous_tree.get_path(Path::new(&from)) {
+                    update.remove(&from);
+                    update.upsert(&to, entry.id(), FileMode::Blob);
+                }
+            }
+        }
+        let staged_tree = update
+            .create_updated(&self.git_repo, &previous_tree)
+            .change_context(MojError::Commit)
+            .attach("Cannot stage renamed classes")?;
+        Ok(Some((staged_tree, message)))
+    }
+
+    /// The staged renames of the commit currently tagged for the version, if it was made from the
+    /// same previous tree to the same tree, as detecting the renames again would find the same.
+    /// That is the staged tree and its message, or `None` if nothing was staged then.
+    fn reusable_staged_renames(
+        &self,
+        version: &Version,
+        previous: &Commit,
+        tree: &Tree,
+    ) -> Option<Option<(Oid, String)>> {
+        let existing = self
+            .git_repo
+            .find_reference(&self.version_reference(&version.id))
+            .ok()?
+            .peel_to_commit()
+            .ok()?;
+        if existing.tree_id() != tree.id() {
+            return None;
+        }
+        let parent = existing.parents().next()?;
+        let staged_summary = format!("{}{}", Self::STAGED_RENAMES_PREFIX, version.id);
+        if parent.summary() == Some(staged_summary.as_str()) {
+            let staged_from = parent.parents().next()?;
+            if staged_from.tree_id() != previous.tree_id() {
+                return None;
+            }
+            let message = parent.message()?.to_string();
+            Some(Some((parent.tree_id(), message)))
+        } else {
+            // Without a note saying renames were looked for, there may have been some after all
+            let renames_staged = self.read_saved_info(&version.id, &existing).renames_staged;
+            (renames_staged && parent.tree_id() == previous.tree_id()).then_some(None)
+        }
+    }
+
+    /// The latest of a version commit and the versions before it that isn't a placeholder, as the
+    /// placeholders have none of the files to compare with.
+    fn last_generated_commit<'r>(&self, commit: &Commit<'r>) -> Option<Commit<'r>> {
+        let mut commit = commit.clone();
+        loop {
+            let version_id = commit.summary()?.strip_prefix("Version ")?.to_string();
+            if self
+                .read_saved_info(&version_id, &commit)
+                .failure()
+                .is_none()
+            {
+                return Some(commit);
+            }
+            commit = self.previous_version_commit(&commit)?;
+        }
+    }
+
+    /// The commit of the version before a commit's version, skipping any staged renames.
+    fn previous_version_commit<'r>(&self, commit: &Commit<'r>) -> Option<Commit<'r>> {
+        let parent = commit.parents().next()?;
+        let is_staged_renames = parent
+            .summary()
+            .is_some_and(|summary| summary.starts_with(Self::STAGED_RENAMES_PREFIX));
+        if is_staged_renames {
+            parent.parents().next()
+        } else {
+            Some(parent)
+        }
+    }
+
+    fn tag(&self, version: &Version, commit: Oid, tagger: &Signature) -> MojResult<()> {
+        let name = self.tag_name(&version.id);
+        let reference = self.version_reference(&version.id);
+        let message = self
+            .options
+            .tags
+            .message
+            .replace("{version}", &version.id)
+            .replace("{type}", &version.type_)
+            .replace(
+                "{release_date}",
+                &version.release_time.format("%Y-%m-%d").to_string(),
+            );
+        if let Ok(existing) = self.git_repo.find_reference(&reference) {
+            let existing_commit = existing.peel_to_commit().ok();
+            if existing_commit.as_ref().map(Commit::id) == Some(commit)
+                && self.is_configured_tag(&existing, &name, &message)
+            {
+                // Already tagged as configured, keep it so the tag object (and any signature)
+                // stays the same
+                return Ok(());
+            }
+            let is_version_commit = existing_commit
+                .as_ref()
+                .and_then(Commit::summary)
+                .is_some_and(|summary| summary == format!("Version {}", version.id));
+            if !is_version_commit {
+                return Err(Report::new(MojError::Tag)
+                    .attach(format!(
+                        "Tag {} already exists, and does not point to a version commit",
+                        name
+                    ))
+                    .attach("Configure a tag prefix to avoid collisions with other tags"));
+            }
+        }
+
+        match self.options.tags.style {
+            TagStyle::Lightweight => {
+                self.git_repo
+                    .tag_lightweight(
+                        &name,
+                        &self.git_repo.find_object(commit, None).unwrap(),
+                        true,
+                    )
+                    .change_context(MojError::Tag)?;
+            }
+            TagStyle::Annotated => match &self.options.tags.sign_command {
+                Some(sign_command) => self.write_signed_tag(
+                    &name,
+                    &reference,
+                    commit,
+                    tagger,
+                    &message,
+                    sign_command,
+                )?,
+                None => {
+                    self.git_repo
+                        .tag(
+                            &name,
+                            &self.git_repo.find_object(commit, None).unwrap(),
+                            tagger,
+                            &message,
+                            true,
+                        )
+                        .change_context(MojError::Tag)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Whether an existing tag has the configured style, name and message, and is signed if tags
+    /// are configured to be.
+    fn is_configured_tag(&self, existing: &git2::Reference, name: &str, message: &str) -> bool {
+        let tag = existing.peel_to_tag().ok();
+        match (self.options.tags.style, tag) {
+            (TagStyle::Lightweight, tag) => tag.is_none(),
+            (TagStyle::Annotated, None) => false,
+            (TagStyle::Annotated, Some(tag)) => {
+                // A signature is appended to the message, and is all that may follow it
+                let signed = match tag
+                    .message()
+                    .and_then(|existing| existing.strip_prefix(message.trim_end()))
+                    .map(str::trim)
+                {
+                    Some("") => false,
+                    Some(rest) if rest.starts_with("-----BEGIN ") => true,
+                    _ => return false,
+                };
+                tag.name() == Some(name) && signed == self.options.tags.sign_command.is_some()
+            }
+        }
+    }
+
+    /// libgit2 can't sign tags, so build the tag object ourselves and append the signature.
+    fn write_signed_tag(
+        &self,
+        name: &str,
+        reference: &str,
+        commit: Oid,
+        tagger: &Signature,
+        message: &str,
+        sign_command: &[String],
+    ) -> MojResult<()> {
+        let offset = tagger.when().offset_minutes();
+        let mut content = format!(
+            "object {}\ntype commit\ntag {}\ntagger {} <{}> {} {}{:02}{:02}\n\n{}\n",
+            commit,
+            name,
+            tagger.name().unwrap_or_default(),
+            tagger.email().unwrap_or_default(),
+            tagger.when().seconds(),
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 60,
+            offset.abs() % 60,
+            message.trim_end(),
+        );
+
+        let (program, args) = sign_command
+            .split_first()
+            .ok_or_else(|| Report::new(MojError::Tag).attach("Tag sign command is empty"))?;
+        let mut child = std::process::Command::new(program)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .change_context(MojError::Tag)
+            .attach("Failed to run tag sign command")
+            .attach_with(|| format!("Command: {:?}", sign_command))?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(content.as_bytes())
+            .change_context(MojError::Tag)
+            .attach("Failed to send tag to sign command")?;
+        let output = child
+            .wait_with_output()
+            .change_context(MojError::Tag)
+            .attach("Failed to run tag sign command")?;
+        if !output.status.success() {
+            return Err(Report::new(MojError::Tag)
+                .attach("Tag sign command failed")
+                .attach(format!("Command: {:?}", sign_command))
+                .attach(format!("Status: {}", output.status)));
+        }
+        content.push_str(&String::from_utf8_lossy(&output.stdout));
+
+        let tag = self
+            .git_repo
+            .odb()
+            .change_context(MojError::Tag)?
+            .write(git2::ObjectType::Tag, content.as_bytes())
+            .change_context(MojError::Tag)
+            .attach("Failed to write signed tag")?;
+        self.git_repo
+            .reference(reference, tag, true, "mojankinator: signed tag")
             .change_context(MojError::Tag)?;
+        Ok(())
+    }
+
+    /// Get the message of the commit currently tagged for the version, if it was made with the
+    /// same tree on top of the same parent tree, as the message would come out the same. Messages
+    /// in an older format, or naming another Java version, are written again.
+    fn reusable_message(
+        &self,
+        version: &Version,
+        saved_info: &SavedInfo,
+        tree: &Tree,
+        parent: Option<&Commit>,
+    ) -> Option<String> {
+        let existing = self
+            .git_repo
+            .find_reference(&self.version_reference(&version.id))
+            .ok()?
+            .peel_to_commit()
+            .ok()?;
+        let existing_parent = self.previous_version_commit(&existing);
+        let same_parent = match (existing_parent, parent) {
+            (Some(a), Some(b)) => a.tree_id() == b.tree_id() && a.summary() == b.summary(),
+            (None, None) => true,
+            _ => false,
+        };
+        if existing.tree_id() != tree.id() || !same_parent {
+            return None;
+        }
+        // Messages on top of a placeholder used to compare with the placeholder itself
+        let parent_failed = parent.is_some_and(|parent| {
+            let version_id = parent.summary().and_then(|s| s.strip_prefix("Version "));
+            version_id.is_some_and(|id| self.read_saved_info(id, parent).failure().is_some())
+        });
+        if parent_failed {
+            return None;
+        }
+        let message = existing.message()?;
+        let mut lines = message.lines();
+        if !lines.clone().any(|line| line.starts_with("Type: ")) {
+            return None;
+        }
+        let java_version = lines.find_map(|line| line.strip_prefix("Java: "));
+        if java_version != saved_info.java_version.map(|v| v.to_string()).as_deref() {
+            return None;
+        }
+        Some(message.to_string())
+    }
+
+    fn version_commit_message(
+        &self,
+        version: &Version,
+        saved_info: &SavedInfo,
+        tree: &Tree,
+        parent: Option<&Commit>,
+    ) -> MojResult<String> {
+        let mut message = format!("Version {}\n\n", version.id);
+        writeln!(message, "Type: {}", version.type_).unwrap();
+        writeln!(
+            message,
+            "Released: {}",
+            version.release_time.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+        .unwrap();
+        if let Some(url) = version.changelog_url() {
+            writeln!(message, "Changelog: {}", url).unwrap();
+        }
+        if let Some(java_version) = saved_info.java_version {
+            writeln!(message, "Java: {}", java_version).unwrap();
+        }
+        if let Some(failure) = saved_info.failure() {
+            writeln!(
+                message,
+                "\nGenerating this version failed, so it only has {}:\n{}",
+                Self::PLACEHOLDER_FILE,
+                failure
+            )
+            .unwrap();
+        } else if let Some(generated) = parent.and_then(|parent| self.last_generated_commit(parent))
+        {
+            let stats = self.diff_stats(
+                &generated.tree().change_context(MojError::Commit)?,
+                tree,
+                DecompileArtifact::DecompiledClasses.path_in_repository(),
+            )?;
+            let previous = generated
+                .summary()
+                .and_then(|s| s.strip_prefix("Version "))
+                .unwrap_or("the previous version");
+            writeln!(message, "\nChanges since {}:", previous).unwrap();
+            writeln!(message, "  Classes added: {}", stats.classes_added).unwrap();
+            writeln!(message, "  Classes removed: {}", stats.classes_removed).unwrap();
+            writeln!(message, "  Classes modified: {}", stats.classes_modified).unwrap();
+            writeln!(
+                message,
+                "  Lines changed: +{} -{}",
+                stats.insertions, stats.deletions
+            )
+            .unwrap();
+        }
+        Ok(message)
+    }
+
+    /// Compute statistics for the changes to the classes in `path` between two trees.
+    pub fn diff_stats(&self, old: &Tree, new: &Tree, path: &str) -> MojResult<DiffStats> {
+        let diff = self
+            .git_repo
+            .diff_tree_to_tree(
+                Some(old),
+                Some(new),
+                Some(git2::DiffOptions::new().pathspec(path)),
+            )
+            .change_context(MojError::Commit)
+            .attach("Cannot diff trees")?;
+        let mut stats = DiffStats::default();
+        for delta in diff.deltas() {
+            let is_class = delta
+                .new_file()
+                .path()
+                .or(delta.old_file().path())
+                .is_some_and(|p| p.extension() == Some("java".as_ref()));
+            if !is_class {
+                continue;
+            }
+            match delta.status() {
+                git2::Delta::Added => stats.classes_added += 1,
+                git2::Delta::Deleted => stats.classes_removed += 1,
+                _ => stats.classes_modified += 1,
+            }
+        }
+        let line_stats = diff
+            .stats()
+            .change_context(MojError::Commit)
+            .attach("Cannot compute diff stats")?;
+        stats.insertions = line_stats.insertions();
+        stats.deletions = line_stats.deletions();
+        Ok(stats)
+    }
+
+    /// Whether the repository has no working tree to check versions out into.
+    pub fn is_bare(&self) -> bool {
+        self.git_repo.is_bare()
+    }
+
+    fn objects_dir(&self) -> PathBuf {
+        self.git_repo.path().join("objects")
+    }
+
+    /// The total size of the object database on disk, in bytes.
+    pub fn object_store_size(&self) -> MojResult<u64> {
+        let mut size = 0;
+        for entry in walkdir::WalkDir::new(self.objects_dir()) {
+            let entry = entry.change_context(MojError::Maintenance)?;
+            if entry.file_type().is_file() {
+                size += entry
+                    .metadata()
+                    .change_context(MojError::Maintenance)?
+                    .len();
+            }
+        }
+        Ok(size)
+    }
+
+    /// Delete the reflogs of the references every run rewrites: `HEAD`, its branch and the info
+    /// notes, as they only keep the commits of previous runs alive. The reflogs of other references
+    /// are left alone. Returns the number of entries removed.
+    pub fn expire_reflogs(&self) -> MojResult<usize> {
+        let mut names = vec!["HEAD".to_string(), Self::INFO_NOTES_REF.to_string()];
+        if let Ok(head) = self.git_repo.find_reference("HEAD") {
+            names.extend(head.symbolic_target().map(str::to_string));
+        }
+        let mut expired = 0;
+        for name in names {
+            let Ok(reflog) = self.git_repo.reflog(&name) else {
+                continue;
+            };
+            if reflog.is_empty() {
+                continue;
+            }
+            expired += reflog.len();
+            self.git_repo
+                .reflog_delete(&name)
+                .change_context(MojError::Maintenance)
+                .attach_with(|| format!("Reference: {}", name))?;
+        }
+        Ok(expired)
+    }
+
+    /// Remove the info notes of commits that are no longer reachable from a branch or tag, such as
+    /// the version commits of previous runs. Returns the number of notes removed.
+    pub fn prune_notes(&self) -> MojResult<usize> {
+        let mut walk = self
+            .git_repo
+            .revwalk()
+            .change_context(MojError::Maintenance)?;
+        for reference in self
+            .git_repo
+            .references()
+            .change_context(MojError::Maintenance)?
+        {
+            let reference = reference.change_context(MojError::Maintenance)?;
+            // The notes don't keep the commits they annotate alive
+            if reference
+                .name()
+                .is_none_or(|name| name.starts_with("refs/notes/"))
+            {
+                continue;
+            }
+            if let Ok(commit) = reference.peel_to_commit() {
+                walk.push(commit.id())
+                    .change_context(MojError::Maintenance)?;
+            }
+        }
+        if let Some(head) = self.head_commit_id() {
+            walk.push(head).change_context(MojError::Maintenance)?;
+        }
+        let reachable = walk
+            .collect::<Result<HashSet<_>, _>>()
+            .change_context(MojError::Maintenance)
+            .attach("Cannot walk reachable commits")?;
+
+        let notes = match self.git_repo.notes(Some(Self::INFO_NOTES_REF)) {
+            Ok(notes) => notes
+                .map(|note| note.map(|(_, annotated)| annotated))
+                .collect::<Result<Vec<_>, _>>()
+                .change_context(MojError::Maintenance)?,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(0),
+            Err(e) => return Err(e).change_context(MojError::Maintenance),
+        };
+        let (name, email) = self.identity();
+        let signature = Signature::now(&name, &email).change_context(MojError::Maintenance)?;
+        let mut removed = 0;
+        for annotated in notes {
+            if reachable.contains(&annotated) {
+                continue;
+            }
+            self.git_repo
+                .note_delete(
+                    annotated,
+                    Some(Self::INFO_NOTES_REF),
+                    &signature,
+                    &signature,
+                )
+                .change_context(MojError::Maintenance)
+                .attach_with(|| format!("Commit: {}", annotated))?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Write every object reachable from a reference, a `HEAD` or the index into a single new
+    /// pack, then delete the old packs and the loose objects the new pack holds. Packs with a
+    /// `.keep` file are left alone. Unreachable objects, such as the commits of previous runs, are
+    /// only pruned once they are older than `prune_after`, like `git gc` does, as they could
+    /// belong to a run that is still going. Those in old packs that aren't that old yet are kept
+    /// as loose objects, as old as their pack.
+    pub fn repack(&self, prune_after: Duration) -> MojResult<RepackStats> {
+        let objects_before = self.count_objects()?;
 
+        let mut roots = Vec::new();
+        for reference in self
+            .git_repo
+            .references()
+            .change_context(MojError::Maintenance)?
+        {
+            let reference = reference.change_context(MojError::Maintenance)?;
+            roots.extend(reference.resolve().ok().and_then(|r| r.target()));
+        }
+        // A detached HEAD isn't a reference, and linked worktrees have HEADs of their own
+        roots.extend(self.head_commit_id());
+        for name in self
+            .git_repo
+            .worktrees()
+            .change_context(MojError::Maintenance)?
+            .iter()
+            .flatten()
+        {
+            let worktree_head = self
+                .git_repo
+                .find_worktree(name)
+                .and_then(|worktree| Repository::open_from_worktree(&worktree))
+                .and_then(|repo| repo.head().map(|head| head.target()));
+            match worktree_head {
+                Ok(head) => roots.extend(head),
+                Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {}
+                Err(e) => {
+                    return Err(e)
+                        .change_context(MojError::Maintenance)
+                        .attach_with(|| format!("Worktree: {}", name))
+                }
+            }
+        }
+        roots.sort();
+        roots.dedup();
+
+        let mut packer = self
+            .git_repo
+            .packbuilder()
+            .change_context(MojError::Maintenance)?;
+        // Use as many threads as there are CPUs to search for deltas
+        packer.set_threads(0);
+        let mut walk = self
+            .git_repo
+            .revwalk()
+            .change_context(MojError::Maintenance)?;
+        for mut oid in roots {
+            // Annotated tags are objects of their own, pointing at the commit
+            while let Ok(tag) = self.git_repo.find_tag(oid) {
+                packer
+                    .insert_object(oid, None)
+                    .change_context(MojError::Maintenance)?;
+                oid = tag.target_id();
+            }
+            if self.git_repo.find_commit(oid).is_ok() {
+                walk.push(oid).change_context(MojError::Maintenance)?;
+            } else {
+                packer
+                    .insert_recursive(oid, None)
+                    .change_context(MojError::Maintenance)?;
+            }
+        }
+        // Inserting whole commits lets the pack builder use paths to find good delta bases
+        packer
+            .insert_walk(&mut walk)
+            .change_context(MojError::Maintenance)
+            .attach("Cannot collect reachable objects")?;
+        if !self.is_bare() {
+            let index = self
+                .git_repo
+                .index()
+                .change_context(MojError::Maintenance)?;
+            for entry in index.iter() {
+                let path = String::from_utf8_lossy(&entry.path).into_owned();
+                packer
+                    .insert_object(entry.id, Some(&path))
+                    .change_context(MojError::Maintenance)?;
+            }
+        }
+
+        let pack_dir = self.objects_dir().join("pack");
+        let old_packs = std::fs::read_dir(&pack_dir)
+            .change_context(MojError::Maintenance)
+            .attach_with(|| format!("Path: {:?}", pack_dir))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .change_context(MojError::Maintenance)?;
+
+        let objects_kept = packer.object_count();
+        if objects_kept == 0 {
+            // Nothing is reachable, so there is no pack to replace the old ones with
+            return Ok(RepackStats {
+                objects_kept,
+                objects_pruned: 0,
+                packs_removed: 0,
+                loose_removed: 0,
+            });
+        }
+        packer
+            .write(&pack_dir, 0)
+            .change_context(MojError::Maintenance)
+            .attach("Cannot write pack")?;
+        let new_pack = packer.name().map(|name| format!("pack-{}", name));
+        // Nothing may be deleted unless the objects are safely in the new pack
+        let new_index = new_pack
+            .as_ref()
+            .map(|name| pack_dir.join(format!("{}.idx", name)))
+            .filter(|path| path.is_file())
+            .ok_or_else(|| {
+                Report::new(MojError::Maintenance).attach("The new pack has no index")
+            })?;
+        let packed = read_pack_index(&new_index)?;
+
+        let old_packs: Vec<_> = old_packs
+            .into_iter()
+            .filter(|path| {
+                let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    return false;
+                };
+                let is_pack_file = path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| Self::PACK_EXTENSIONS.contains(&extension));
+                stem.starts_with("pack-")
+                    && is_pack_file
+                    && Some(stem) != new_pack.as_deref()
+                    && !pack_dir.join(format!("{}.keep", stem)).exists()
+            })
+            .collect();
+
+        let prune_before = SystemTime::now()
+            .checked_sub(prune_after)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        for path in &old_packs {
+            if path.extension() == Some("pack".as_ref()) {
+                self.unpack_unreachable(path, &packed, prune_before)?;
+            }
+        }
+
+        let mut packs_removed = 0;
+        for path in old_packs {
+            if path.extension() == Some("pack".as_ref()) {
+                packs_removed += 1;
+            }
+            std::fs::remove_file(&path)
+                .change_context(MojError::Maintenance)
+                .attach_with(|| format!("Path: {:?}", path))?;
+        }
+
+        let mut loose_removed = 0;
+        for entry in std::fs::read_dir(self.objects_dir()).change_context(MojError::Maintenance)? {
+            let dir = entry.change_context(MojError::Maintenance)?.path();
+            let Some(prefix) = dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .filter(|name| name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit()))
+            else {
+                continue;
+            };
+            for entry in std::fs::read_dir(&dir).change_context(MojError::Maintenance)? {
+                let entry = entry.change_context(MojError::Maintenance)?;
+                let Some(oid) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|rest| Oid::from_str(&format!("{}{}", prefix, rest)).ok())
+                else {
+                    continue;
+                };
+                let expired = || {
+                    entry
+                        .metadata()
+                        .and_then(|metadata| metadata.modified())
+                        .is_ok_and(|modified| modified < prune_before)
+                };
+                if packed.contains(&oid) || expired() {
+                    std::fs::remove_file(entry.path())
+                        .change_context(MojError::Maintenance)
+                        .attach_with(|| format!("Path: {:?}", entry.path()))?;
+                    loose_removed += 1;
+                }
+            }
+            // Only succeeds once every object in it is gone
+            let _ = std::fs::remove_dir(&dir);
+        }
+
+        Ok(RepackStats {
+            objects_kept,
+            objects_pruned: objects_before.saturating_sub(self.count_objects()?),
+            packs_removed,
+            loose_removed,
+        })
+    }
+
+    /// Write the objects of an old pack that the new pack doesn't hold out as loose objects, unless
+    /// the pack is older than `prune_before`. They get the time of the pack, so they are pruned
+    /// once it would have been.
+    fn unpack_unreachable(
+        &self,
+        pack: &Path,
+        packed: &HashSet<Oid>,
+        prune_before: SystemTime,
+    ) -> MojResult<()> {
+        let modified = std::fs::metadata(pack)
+            .and_then(|metadata| metadata.modified())
+            .change_context(MojError::Maintenance)
+            .attach_with(|| format!("Path: {:?}", pack))?;
+        if modified < prune_before {
+            return Ok(());
+        }
+        let unreachable: Vec<_> = read_pack_index(&pack.with_extension("idx"))?
+            .into_iter()
+            .filter(|oid| !packed.contains(oid))
+            .collect();
+        if unreachable.is_empty() {
+            return Ok(());
+        }
+        // libgit2 doesn't write objects that a pack already has, so they are written to an empty
+        // repository first
+        let staging =
+            tempfile::tempdir_in(self.git_repo.path()).change_context(MojError::Maintenance)?;
+        let staging_repo =
+            Repository::init_bare(staging.path()).change_context(MojError::Maintenance)?;
+        let staging_odb = staging_repo.odb().change_context(MojError::Maintenance)?;
+        let odb = self.git_repo.odb().change_context(MojError::Maintenance)?;
+        for oid in unreachable {
+            let hex = oid.to_string();
+            let (prefix, rest) = hex.split_at(2);
+            let loose = self.objects_dir().join(prefix).join(rest);
+            if loose.exists() {
+                continue;
+            }
+            let object = odb
+                .read(oid)
+                .change_context(MojError::Maintenance)
+                .attach_with(|| format!("Object: {}", oid))?;
+            staging_odb
+                .write(object.kind(), object.data())
+                .change_context(MojError::Maintenance)
+                .attach_with(|| format!("Object: {}", oid))?;
+            let staged = staging.path().join("objects").join(prefix).join(rest);
+            std::fs::File::open(&staged)
+                .and_then(|file| file.set_modified(modified))
+                .and_then(|_| std::fs::create_dir_all(loose.parent().unwrap()))
+                .and_then(|_| std::fs::rename(&staged, &loose))
+                .change_context(MojError::Maintenance)
+                .attach_with(|| format!("Path: {:?}", loose))?;
+        }
         Ok(())
     }
 
+    /// The files that make up a pack, which are replaced by the new pack when repacking.
+    const PACK_EXTENSIONS: [&'static str; 4] = ["pack", "idx", "rev", "bitmap"];
+
+    /// The number of distinct objects in the object database.
+    fn count_objects(&self) -> MojResult<usize> {
+        let odb = self.git_repo.odb().change_context(MojError::Maintenance)?;
+        // Pick up packs written or deleted since the database was opened
+        odb.refresh().change_context(MojError::Maintenance)?;
+        let mut objects = HashSet::new();
+        odb.foreach(|oid| {
+            objects.insert(*oid);
+            true
+        })
+        .change_context(MojError::Maintenance)
+        .attach("Cannot list objects")?;
+        Ok(objects.len())
+    }
+
+    /// The commit at the tip of the branch, if there is one yet.
+    pub fn head_commit_id(&self) -> Option<Oid> {
+        self.git_repo.head().ok()?.target()
+    }
+
     pub fn checkout_head(&self) -> MojResult<()> {
         self.git_repo
             .checkout_head(Some(&mut git2::build::CheckoutBuilder::new().force()))
@@ -222,50 +1629,232 @@ impl MojRepository {
     }
 }
 
-fn add_file_to_index(
-    index: &mut Index,
-    root: &Path,
-    repo_root: &str,
+/// Read the objects listed in a version 2 pack index, which is what libgit2 writes.
+fn read_pack_index(path: &Path) -> MojResult<HashSet<Oid>> {
+    const HEADER: [u8; 8] = [0xff, b't', b'O', b'c', 0, 0, 0, 2];
+    const FANOUT_END: usize = HEADER.len() + 256 * 4;
+    let data = std::fs::read(path)
+        .change_context(MojError::Maintenance)
+        .attach_with(|| format!("Path: {:?}", path))?;
+    let invalid = || {
+        Report::new(MojError::Maintenance)
+            .attach("Invalid pack index")
+            .attach(format!("Path: {:?}", path))
+    };
+    if data.len() < FANOUT_END || data[..HEADER.len()] != HEADER {
+        return Err(invalid());
+    }
+    // The last fanout entry counts every object
+    let count = u32::from_be_bytes(data[FANOUT_END - 4..FANOUT_END].try_into().unwrap()) as usize;
+    data.get(FANOUT_END..FANOUT_END + count * 20)
+        .ok_or_else(invalid)?
+        .chunks(20)
+        .map(|oid| Oid::from_bytes(oid).map_err(|_| invalid()))
+        .collect()
+}
+
+/// Write the file to the object database as a blob. The file is read and hashed only once, and
+/// the object database skips writing blobs it already has. Every blob gets the same mode, so
+/// permissions in the work area can't cause mode changes between versions.
+fn write_blob(
+    repo: &Repository,
     file: &Path,
-) -> MojResult<()> {
-    let stat = file
-        .metadata()
+    normalization: &Normalization,
+) -> MojResult<(Oid, i32)> {
+    let contents = std::fs::read(file)
         .change_context(MojError::Commit)
         .attach_with(|| format!("Path: {:?}", file))?;
-    assert!(stat.is_file(), "Only files can be added to the index");
-    let index_entry = IndexEntry {
-        ctime: IndexTime::new(
-            stat.ctime().try_into().unwrap(),
-            stat.ctime_nsec().try_into().unwrap(),
-        ),
-        mtime: IndexTime::new(
-            stat.mtime().try_into().unwrap(),
-            stat.mtime_nsec().try_into().unwrap(),
-        ),
-        dev: stat.dev().try_into().unwrap(),
-        ino: stat.ino().try_into().unwrap(),
-        mode: stat.mode(),
-        uid: stat.uid(),
-        gid: stat.gid(),
-        file_size: stat.size().try_into().unwrap(),
-        id: Oid::hash_file(git2::ObjectType::Blob, file)
-            .change_context(MojError::Commit)
-            .attach_with(|| format!("Path: {:?}", file))?,
-        flags: 0,
-        flags_extended: 0,
-        path: Path::new(repo_root)
-            .join(file.strip_prefix(root).unwrap())
-            .as_os_str()
-            .as_bytes()
-            .to_vec(),
-    };
-    let file_contents = std::fs::read(file)
+    let oid = repo
+        .blob(&normalization.apply(contents))
         .change_context(MojError::Commit)
         .attach_with(|| format!("Path: {:?}", file))?;
-    index
-        .add_frombuffer(&index_entry, &file_contents)
-        .change_context(MojError::Commit)
-        .attach_with(|| format!("Path: {:?}", file))
+    Ok((oid, FileMode::Blob.into()))
+}
+
+/// A directory of a tree being built.
+#[derive(Debug, Default)]
+struct TreeNode {
+    /// Objects that are already written, by name, with their file mode.
+    entries: BTreeMap<String, (Oid, i32)>,
+    subtrees: BTreeMap<String, TreeNode>,
+}
+
+impl TreeNode {
+    /// Insert an object at a `/`-separated path, creating the directories leading up to it.
+    fn insert(&mut self, path: &str, oid: Oid, mode: i32) {
+        match path.split_once('/') {
+            Some((directory, rest)) => self
+                .subtrees
+                .entry(directory.to_string())
+                .or_default()
+                .insert(rest, oid, mode),
+            None => {
+                self.entries.insert(path.to_string(), (oid, mode));
+            }
+        }
+    }
+
+    fn write(&self, repo: &Repository) -> Result<Oid, git2::Error> {
+        let mut builder = repo.treebuilder(None)?;
+        for (name, (oid, mode)) in &self.entries {
+            builder.insert(name, *oid, *mode)?;
+        }
+        for (name, subtree) in &self.subtrees {
+            builder.insert(name, subtree.write(repo)?, FileMode::Tree.into())?;
+        }
+        builder.write()
+    }
+}
+
+/// A version commit, as found by its tag.
+#[derive(Debug, Clone)]
+pub struct VersionCommit {
+    pub id: String,
+    pub release_time: i64,
+    pub commit: Oid,
+    pub tree: Oid,
+    pub message: String,
+    pub info: SavedInfo,
+}
+
+impl VersionCommit {
+    /// Whether the commit is a placeholder for a version that failed to generate, which has none
+    /// of the artifacts.
+    pub fn is_failed(&self) -> bool {
+        self.info.failure().is_some()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChangeKind {
+    Added,
+    Removed,
+    Modified,
+    Renamed,
+}
+
+/// A file that changed between two versions. Only renamed and modified files have both paths.
+#[derive(Debug, Serialize)]
+pub struct FileChange {
+    pub kind: FileChangeKind,
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl FileChange {
+    fn new(delta: &git2::DiffDelta, insertions: usize, deletions: usize) -> Self {
+        let kind = match delta.status() {
+            git2::Delta::Added => FileChangeKind::Added,
+            git2::Delta::Deleted => FileChangeKind::Removed,
+            git2::Delta::Renamed => FileChangeKind::Renamed,
+            _ => FileChangeKind::Modified,
+        };
+        let path_of =
+            |file: git2::DiffFile| file.path().map(|path| path.to_string_lossy().into_owned());
+        FileChange {
+            kind,
+            old_path: path_of(delta.old_file()).filter(|_| kind != FileChangeKind::Added),
+            new_path: path_of(delta.new_file()).filter(|_| kind != FileChangeKind::Removed),
+            insertions,
+            deletions,
+        }
+    }
+
+    /// The path of the file in the newer version, or in the older one if it was removed.
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    /// The line, without its line ending.
+    pub content: String,
+}
+
+/// A changed file with its changed lines, grouped into hunks. Binary files have no hunks.
+#[derive(Debug, Serialize)]
+pub struct FileDiff {
+    #[serde(flatten)]
+    pub change: FileChange,
+    pub binary: bool,
+    pub hunks: Vec<Vec<DiffLine>>,
+}
+
+#[derive(Debug)]
+pub struct RepackStats {
+    pub objects_kept: usize,
+    pub objects_pruned: usize,
+    pub packs_removed: usize,
+    pub loose_removed: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct DiffStats {
+    pub classes_added: usize,
+    pub classes_removed: usize,
+    pub classes_modified: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// How the contents of text files are normalized before they are committed. Binary files, which
+/// are detected the same way git does, are never changed.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Normalization {
+    /// Convert CRLF line endings to LF.
+    pub line_endings: bool,
+    /// Strip whitespace from the end of every line.
+    pub trailing_whitespace: bool,
+}
+
+impl Normalization {
+    /// How much of a file git looks at to decide whether it is binary.
+    const BINARY_CHECK_LENGTH: usize = 8000;
+
+    fn apply(&self, contents: Vec<u8>) -> Vec<u8> {
+        let is_binary = contents[..contents.len().min(Self::BINARY_CHECK_LENGTH)].contains(&0);
+        if !(self.line_endings || self.trailing_whitespace) || is_binary {
+            return contents;
+        }
+        let mut normalized = Vec::with_capacity(contents.len());
+        for line in contents.split_inclusive(|&b| b == b'\n') {
+            let (mut body, mut ending) = if let Some(body) = line.strip_suffix(b"\r\n") {
+                (body, &b"\r\n"[..])
+            } else if let Some(body) = line.strip_suffix(b"\n") {
+                (body, &b"\n"[..])
+            } else {
+                (line, &b""[..])
+            };
+            if self.line_endings && ending == b"\r\n" {
+                ending = b"\n";
+            }
+            if self.trailing_whitespace {
+                body = body.trim_ascii_end();
+            }
+            normalized.extend_from_slice(body);
+            normalized.extend_from_slice(ending);
+        }
+        normalized
+    }
 }
 
 #[derive(Debug)]
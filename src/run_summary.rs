@@ -0,0 +1,152 @@
+use crate::colorize::InfoColors;
+use crate::decompiler::DecompileArtifact;
+use crate::{error_message, MojError, MojResult, SavedInfo};
+use chrono::{DateTime, Utc};
+use error_stack::{Report, ResultExt};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default, clap::ValueEnum)]
+pub enum SummaryFormat {
+    /// A line counting the versions on stderr.
+    #[default]
+    Text,
+    /// The full summary as JSON on stdout.
+    Json,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionOutcome {
+    /// The version wasn't in the repository yet.
+    Added,
+    /// The version was already up to date, and its tree was committed again as is.
+    Reused,
+    /// Some or all artifacts of the version were generated again.
+    Regenerated,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionSummary {
+    pub id: String,
+    pub outcome: VersionOutcome,
+    pub duration_secs: f64,
+    /// The version of each artifact in the commit, by its directory in the repository.
+    pub artifacts: BTreeMap<&'static str, u32>,
+    /// The artifacts that were generated in this run.
+    pub generated_artifacts: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    /// The version being processed, if the failure was caused by one.
+    pub version: Option<String>,
+    pub error: String,
+}
+
+/// What an update did, for CI and other tools.
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    pub success: bool,
+    pub added: Vec<String>,
+    pub reused: Vec<String>,
+    pub regenerated: Vec<String>,
+    /// Versions that were in the repository before, but are no longer on the branch.
+    pub removed: Vec<String>,
+    pub versions: Vec<VersionSummary>,
+    pub failures: Vec<Failure>,
+    /// The commit at the tip of the branch after the run.
+    pub head: Option<String>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl RunSummary {
+    pub fn start() -> Self {
+        RunSummary {
+            started_at: Utc::now(),
+            duration_secs: 0.0,
+            success: false,
+            added: Vec::new(),
+            reused: Vec::new(),
+            regenerated: Vec::new(),
+            removed: Vec::new(),
+            versions: Vec::new(),
+            failures: Vec::new(),
+            head: None,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn add_version(
+        &mut self,
+        id: &str,
+        outcome: VersionOutcome,
+        duration: Duration,
+        info: &SavedInfo,
+        generated: &[DecompileArtifact],
+    ) {
+        match outcome {
+            VersionOutcome::Added => &mut self.added,
+            VersionOutcome::Reused => &mut self.reused,
+            VersionOutcome::Regenerated => &mut self.regenerated,
+        }
+        .push(id.to_string());
+        self.versions.push(VersionSummary {
+            id: id.to_string(),
+            outcome,
+            duration_secs: duration.as_secs_f64(),
+            artifacts: DecompileArtifact::all()
+                .iter()
+                .map(|&artifact| {
+                    (
+                        artifact.path_in_repository(),
+                        info.get_artifact_version(artifact),
+                    )
+                })
+                .collect(),
+            generated_artifacts: generated.iter().map(|a| a.path_in_repository()).collect(),
+        });
+    }
+
+    pub fn add_failure(&mut self, version: Option<&str>, report: &Report<MojError>) {
+        self.failures.push(Failure {
+            version: version.map(str::to_string),
+            error: error_message(report),
+        });
+    }
+
+    pub fn finish(&mut self, success: bool) {
+        self.success = success;
+        self.duration_secs = self.started.elapsed().as_secs_f64();
+    }
+
+    /// Print the summary in the given format, and write it as JSON to the file if there is one.
+    pub fn emit(&self, format: SummaryFormat, file: Option<&Path>) -> MojResult<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map(|json| json + "\n")
+            .change_context(MojError::Summary)
+            .attach("Failed to serialize run summary")?;
+        match format {
+            SummaryFormat::Text => log::info!(
+                "Summary: {} added, {} reused, {} regenerated, {} removed, {} failed",
+                self.added.len().as_important_value(),
+                self.reused.len().as_important_value(),
+                self.regenerated.len().as_important_value(),
+                self.removed.len().as_important_value(),
+                self.failures.len().as_important_value()
+            ),
+            SummaryFormat::Json => print!("{}", json),
+        }
+        if let Some(file) = file {
+            std::fs::write(file, json)
+                .change_context(MojError::Summary)
+                .attach_with(|| format!("Path: {:?}", file))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,593 @@
+use crate::class_artifacts::{api_signatures_path, parse_api_signatures};
+use crate::colorize::InfoColors;
+use crate::logging;
+use crate::repository::{MojRepository, VersionCommit};
+use crate::{open_repository, version_file_name, Config, MojError, MojResult};
+use error_stack::{Report, ResultExt};
+use git2::Oid;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+const INDEX_PATH: &str = "./search-index";
+/// The version of the index format. Indexes of other versions are rebuilt from scratch.
+const INDEX_VERSION: u32 = 1;
+/// The zstd level to compress segments with. Segments are written once and read on every search.
+const ZSTD_LEVEL: i32 = 9;
+/// How many matching lines are shown for each stretch of versions a text was found in.
+const LINES_PER_MATCH: usize = 3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, clap::ValueEnum)]
+pub enum SearchFormat {
+    Text,
+    Json,
+}
+
+/// Which segments the index has, and what each was built from.
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    index_version: u32,
+    /// The trees each segment was built from, by version id.
+    segments: BTreeMap<String, SegmentStamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct SegmentStamp {
+    previous_tree: Option<String>,
+    tree: String,
+}
+
+/// What changed in a version since the previous one. Replaying the segments in order gives the
+/// symbols and files of every version.
+#[derive(Serialize, Deserialize, Default)]
+struct Segment {
+    /// Symbols added or whose declaration changed, with their new declaration.
+    symbols_changed: BTreeMap<String, String>,
+    symbols_removed: Vec<String>,
+    /// Text files added or changed, as `(path, blob)`.
+    files_changed: Vec<(String, String)>,
+    /// Files removed or no longer text.
+    files_removed: Vec<String>,
+    /// For each lowercased word, the changed files containing it, by index in `files_changed`.
+    words: BTreeMap<String, Vec<u32>>,
+}
+
+fn segment_path(index: &Path, version_id: &str) -> PathBuf {
+    index
+        .join("segments")
+        .join(format!("{}.json.zst", version_file_name(version_id)))
+}
+
+/// The distinct lowercased words of a text. Words are runs of characters that can make up a Java
+/// identifier.
+fn words(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn as_text(contents: &[u8]) -> Option<&str> {
+    std::str::from_utf8(contents)
+        .ok()
+        .filter(|_| !contents.contains(&0))
+}
+
+fn read_symbols(repo: &MojRepository, tree: Option<Oid>) -> MojResult<BTreeMap<String, String>> {
+    let Some(tree) = tree else {
+        return Ok(BTreeMap::new());
+    };
+    let contents = repo
+        .read_tree_file(tree, &api_signatures_path())?
+        .unwrap_or_default();
+    Ok(parse_api_signatures(&String::from_utf8_lossy(&contents))
+        .map(|(key, declaration)| (key.to_string(), declaration.to_string()))
+        .collect())
+}
+
+fn build_segment(repo: &MojRepository, previous: Option<Oid>, tree: Oid) -> MojResult<Segment> {
+    let mut segment = Segment::default();
+
+    let old_symbols = read_symbols(repo, previous)?;
+    let new_symbols = read_symbols(repo, Some(tree))?;
+    for (key, declaration) in &new_symbols {
+        if old_symbols.get(key) != Some(declaration) {
+            segment
+                .symbols_changed
+                .insert(key.clone(), declaration.clone());
+        }
+    }
+    segment.symbols_removed = old_symbols
+        .into_keys()
+        .filter(|key| !new_symbols.contains_key(key))
+        .collect();
+
+    let old_files: HashMap<String, Oid> = match previous {
+        Some(previous) => repo.tree_files(previous)?.into_iter().collect(),
+        None => HashMap::new(),
+    };
+    let new_files = repo.tree_files(tree)?;
+    let new_paths: HashSet<&str> = new_files.iter().map(|(path, _)| path.as_str()).collect();
+    let mut changed = Vec::new();
+    for (path, oid) in &new_files {
+        if old_files.get(path) != Some(oid) {
+            changed.push((path.clone(), *oid, repo.read_blob(*oid)?));
+        }
+    }
+    let changed_words: Vec<Option<BTreeSet<String>>> = changed
+        .par_iter()
+        .map(|(_, _, contents)| as_text(contents).map(words))
+        .collect();
+    for ((path, oid, _), file_words) in changed.into_iter().zip(changed_words) {
+        let Some(file_words) = file_words else {
+            segment.files_removed.push(path);
+            continue;
+        };
+        let file = segment.files_changed.len() as u32;
+        segment.files_changed.push((path, oid.to_string()));
+        for word in file_words {
+            segment.words.entry(word).or_default().push(file);
+        }
+    }
+    segment.files_removed.extend(
+        old_files
+            .into_keys()
+            .filter(|path| !new_paths.contains(path.as_str())),
+    );
+    Ok(segment)
+}
+
+fn write_segment(path: &Path, segment: &Segment) -> MojResult<()> {
+    let json = serde_json::to_vec(segment)
+        .change_context(MojError::Search)
+        .attach("Failed to serialize index segment")?;
+    let compressed = zstd::encode_all(json.as_slice(), ZSTD_LEVEL)
+        .change_context(MojError::Search)
+        .attach_with(|| format!("Path: {:?}", path))?;
+    std::fs::write(path, compressed)
+        .change_context(MojError::Search)
+        .attach_with(|| format!("Path: {:?}", path))
+}
+
+fn read_segment(path: &Path) -> MojResult<Segment> {
+    let compressed = std::fs::read(path)
+        .change_context(MojError::Search)
+        .attach_with(|| format!("Path: {:?}", path))?;
+    let json = zstd::decode_all(compressed.as_slice())
+        .change_context(MojError::Search)
+        .attach_with(|| format!("Path: {:?}", path))?;
+    serde_json::from_slice(&json)
+        .change_context(MojError::Search)
+        .attach_with(|| format!("Path: {:?}", path))
+}
+
+fn write_manifest(index: &Path, manifest: &Manifest) -> MojResult<()> {
+    let path = index.join("manifest.json");
+    let json = serde_json::to_string_pretty(manifest)
+        .change_context(MojError::Search)
+        .attach("Failed to serialize index manifest")?;
+    std::fs::write(&path, json)
+        .change_context(MojError::Search)
+        .attach_with(|| format!("Path: {:?}", path))
+}
+
+/// Bring the index in line with the versions in the repository. A version's segment is only built
+/// again if its tree or the tree of the version before it changed.
+fn update_index(repo: &MojRepository, index: &Path, versions: &[VersionCommit]) -> MojResult<()> {
+    let segments_dir = index.join("segments");
+    std::fs::create_dir_all(&segments_dir)
+        .change_context(MojError::Search)
+        .attach_with(|| format!("Path: {:?}", segments_dir))?;
+    let mut manifest: Manifest = std::fs::read(index.join("manifest.json"))
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .filter(|manifest: &Manifest| manifest.index_version == INDEX_VERSION)
+        .unwrap_or_default();
+    manifest.index_version = INDEX_VERSION;
+
+    let stamps: Vec<SegmentStamp> = versions
+        .iter()
+        .enumerate()
+        .map(|(i, version)| SegmentStamp {
+            previous_tree: i.checked_sub(1).map(|i| versions[i].tree.to_string()),
+            tree: version.tree.to_string(),
+        })
+        .collect();
+    let outdated: Vec<usize> = (0..versions.len())
+        .filter(|&i| {
+            manifest.segments.get(&versions[i].id) != Some(&stamps[i])
+                || !segment_path(index, &versions[i].id).exists()
+        })
+        .collect();
+    if !outdated.is_empty() {
+        log::info!(
+            "Indexing {} versions...",
+            outdated.len().as_important_value()
+        );
+        let progress_bar = logging::progress_bar(
+            outdated.len() as u64,
+            "Index progress: {bar:40.white/blue} {pos:.cyan}/{len:.cyan} ({msg})",
+        );
+        for i in outdated {
+            let version = &versions[i];
+            progress_bar.set_message(version.id.clone());
+            let previous = i.checked_sub(1).map(|i| versions[i].tree);
+            let segment = build_segment(repo, previous, version.tree)?;
+            write_segment(&segment_path(index, &version.id), &segment)?;
+            manifest
+                .segments
+                .insert(version.id.clone(), stamps[i].clone());
+            // Save as we go, so an interrupted run doesn't have to start over
+            write_manifest(index, &manifest)?;
+            progress_bar.inc(1);
+        }
+        progress_bar.finish_and_clear();
+    }
+
+    let current: HashSet<&str> = versions.iter().map(|v| v.id.as_str()).collect();
+    let stale: Vec<String> = manifest
+        .segments
+        .keys()
+        .filter(|id| !current.contains(id.as_str()))
+        .cloned()
+        .collect();
+    for id in stale {
+        manifest.segments.remove(&id);
+        let _ = std::fs::remove_file(segment_path(index, &id));
+    }
+    write_manifest(index, &manifest)
+}
+
+/// The history of a symbol across the versions.
+#[derive(Debug, Serialize)]
+pub struct SymbolHistory {
+    pub symbol: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    /// Whether the symbol is missing from the newest version.
+    pub removed: bool,
+    /// Every version where the symbol appeared, changed or disappeared, oldest first.
+    pub changes: Vec<SymbolChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SymbolChange {
+    pub version: String,
+    /// The new declaration, or `None` if the symbol was removed.
+    pub declaration: Option<String>,
+}
+
+/// Find the symbols whose key contains the pattern, ignoring case, and follow them through every
+/// version.
+fn search_symbols(
+    versions: &[VersionCommit],
+    segments: &[Segment],
+    pattern: &str,
+) -> Vec<SymbolHistory> {
+    let pattern = pattern.to_lowercase();
+    let mut histories = BTreeMap::<&str, SymbolHistory>::new();
+    for (version, segment) in versions.iter().zip(segments) {
+        for (symbol, declaration) in &segment.symbols_changed {
+            if !symbol.to_lowercase().contains(&pattern) {
+                continue;
+            }
+            let history = histories.entry(symbol).or_insert_with(|| SymbolHistory {
+                symbol: symbol.clone(),
+                first_seen: version.id.clone(),
+                last_seen: String::new(),
+                removed: false,
+                changes: Vec::new(),
+            });
+            history.removed = false;
+            history.changes.push(SymbolChange {
+                version: version.id.clone(),
+                declaration: Some(declaration.clone()),
+            });
+        }
+        for symbol in &segment.symbols_removed {
+            if let Some(history) = histories.get_mut(symbol.as_str()) {
+                history.removed = true;
+                history.changes.push(SymbolChange {
+                    version: version.id.clone(),
+                    declaration: None,
+                });
+            }
+        }
+    }
+    let position: HashMap<&str, usize> = versions
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (v.id.as_str(), i))
+        .collect();
+    let newest = versions.last().map(|v| v.id.clone()).unwrap_or_default();
+    let mut histories: Vec<SymbolHistory> = histories.into_values().collect();
+    for history in &mut histories {
+        history.last_seen = if history.removed {
+            let removed_in = position[history.changes.last().unwrap().version.as_str()];
+            versions[removed_in - 1].id.clone()
+        } else {
+            newest.clone()
+        };
+    }
+    histories
+}
+
+/// A stretch of consecutive versions in which a file contained the same matching lines.
+#[derive(Debug, Serialize)]
+pub struct TextMatch {
+    pub first_version: String,
+    pub last_version: String,
+    /// The matching lines, as line number in the last version and line.
+    pub lines: Vec<(usize, String)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileMatches {
+    pub path: String,
+    pub matches: Vec<TextMatch>,
+}
+
+/// The versions a file had the same blob in, by index.
+struct Presence {
+    path: String,
+    blob: String,
+    first: usize,
+    last: usize,
+}
+
+/// Find the files containing the text, ignoring case, and the versions they contained it in.
+fn search_text(
+    repo: &MojRepository,
+    versions: &[VersionCommit],
+    segments: &[Segment],
+    text: &str,
+) -> MojResult<Vec<FileMatches>> {
+    let query_words = words(text);
+    if query_words.is_empty() {
+        return Err(Report::new(MojError::UserError)
+            .attach("The text to search for must contain at least one letter or digit"));
+    }
+
+    // Replay the segments, keeping the stretches of versions in which a file could contain the
+    // text according to its words
+    let mut open = HashMap::<String, (String, usize, bool)>::new();
+    let mut candidates = Vec::new();
+    let mut close = |path: String, (blob, first, candidate): (String, usize, bool), last| {
+        if candidate {
+            candidates.push(Presence {
+                path,
+                blob,
+                first,
+                last,
+            });
+        }
+    };
+    for (i, segment) in segments.iter().enumerate() {
+        let mut matching: Option<HashSet<u32>> = None;
+        for query_word in &query_words {
+            let files: HashSet<u32> = segment
+                .words
+                .iter()
+                .filter(|(word, _)| word.contains(query_word.as_str()))
+                .flat_map(|(_, files)| files.iter().copied())
+                .collect();
+            matching = Some(match matching {
+                Some(matching) => &matching & &files,
+                None => files,
+            });
+        }
+        let matching = matching.unwrap_or_default();
+        for path in &segment.files_removed {
+            if let Some(presence) = open.remove(path) {
+                close(path.clone(), presence, i - 1);
+            }
+        }
+        for (file, (path, blob)) in segment.files_changed.iter().enumerate() {
+            if let Some(presence) = open.remove(path) {
+                close(path.clone(), presence, i - 1);
+            }
+            let candidate = matching.contains(&(file as u32));
+            open.insert(path.clone(), (blob.clone(), i, candidate));
+        }
+    }
+    for (path, presence) in open {
+        close(path, presence, segments.len() - 1);
+    }
+
+    // Check the candidates against the actual text
+    let needle = text.to_lowercase();
+    let mut lines_by_blob = HashMap::<String, Vec<(usize, String)>>::new();
+    let mut by_path = BTreeMap::<String, Vec<(usize, usize, Vec<(usize, String)>)>>::new();
+    candidates.sort_by_key(|presence| presence.first);
+    for presence in candidates {
+        if !lines_by_blob.contains_key(&presence.blob) {
+            let oid = Oid::from_str(&presence.blob).change_context(MojError::Search)?;
+            let contents = repo.read_blob(oid)?;
+            let lines = String::from_utf8_lossy(&contents)
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| line.to_lowercase().contains(&needle))
+                .map(|(n, line)| (n + 1, line.trim().to_string()))
+                .collect();
+            lines_by_blob.insert(presence.blob.clone(), lines);
+        }
+        let lines = &lines_by_blob[&presence.blob];
+        if lines.is_empty() {
+            continue;
+        }
+        let stretches = by_path.entry(presence.path).or_default();
+        // Merge with the previous stretch if only other lines of the file changed
+        let same_lines = |other: &[(usize, String)]| {
+            other.len() == lines.len() && other.iter().zip(lines).all(|(a, b)| a.1 == b.1)
+        };
+        match stretches.last_mut() {
+            Some(last) if last.1 + 1 == presence.first && same_lines(&last.2) => {
+                last.1 = presence.last;
+                last.2 = lines.clone();
+            }
+            _ => stretches.push((presence.first, presence.last, lines.clone())),
+        }
+    }
+
+    let mut files: Vec<FileMatches> = by_path
+        .into_iter()
+        .map(|(path, stretches)| FileMatches {
+            path,
+            matches: stretches
+                .into_iter()
+                .map(|(first, last, lines)| TextMatch {
+                    first_version: versions[first].id.clone(),
+                    last_version: versions[last].id.clone(),
+                    lines,
+                })
+                .collect(),
+        })
+        .collect();
+    // Show the files in which the text first appeared first
+    let position: HashMap<&str, usize> = versions
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (v.id.as_str(), i))
+        .collect();
+    files.sort_by_key(|file| position[file.matches[0].first_version.as_str()]);
+    Ok(files)
+}
+
+fn render_symbols(histories: &[SymbolHistory], limit: usize) -> String {
+    let mut out = String::new();
+    for history in histories.iter().take(limit) {
+        writeln!(out, "{}", history.symbol).unwrap();
+        let changed_in: Vec<&str> = history
+            .changes
+            .iter()
+            .skip(1)
+            .filter(|change| change.declaration.is_some())
+            .map(|change| change.version.as_str())
+            .collect();
+        write!(
+            out,
+            "  first seen {}, last seen {}",
+            history.first_seen, history.last_seen
+        )
+        .unwrap();
+        if !changed_in.is_empty() {
+            write!(out, ", changed in {}", changed_in.join(", ")).unwrap();
+        }
+        writeln!(out).unwrap();
+        for change in &history.changes {
+            match &change.declaration {
+                Some(declaration) => writeln!(out, "  {}: {}", change.version, declaration),
+                None => writeln!(out, "  {}: removed", change.version),
+            }
+            .unwrap();
+        }
+    }
+    if histories.len() > limit {
+        writeln!(out, "({} more symbols not shown)", histories.len() - limit).unwrap();
+    }
+    out
+}
+
+fn render_text_matches(files: &[FileMatches], limit: usize) -> String {
+    let mut out = String::new();
+    for file in files.iter().take(limit) {
+        writeln!(out, "{}", file.path).unwrap();
+        for stretch in &file.matches {
+            if stretch.first_version == stretch.last_version {
+                writeln!(out, "  {}:", stretch.first_version).unwrap();
+            } else {
+                writeln!(
+                    out,
+                    "  {} to {}:",
+                    stretch.first_version, stretch.last_version
+                )
+                .unwrap();
+            }
+            for (number, line) in stretch.lines.iter().take(LINES_PER_MATCH) {
+                writeln!(out, "    {}: {}", number, line).unwrap();
+            }
+            if stretch.lines.len() > LINES_PER_MATCH {
+                writeln!(
+                    out,
+                    "    ({} more lines)",
+                    stretch.lines.len() - LINES_PER_MATCH
+                )
+                .unwrap();
+            }
+        }
+    }
+    if files.len() > limit {
+        writeln!(out, "({} more files not shown)", files.len() - limit).unwrap();
+    }
+    out
+}
+
+fn to_json(value: &impl Serialize) -> MojResult<String> {
+    serde_json::to_string_pretty(value)
+        .map(|json| json + "\n")
+        .change_context(MojError::Search)
+        .attach("Failed to serialize search results")
+}
+
+/// Update the search index with the versions committed since the last search, then search every
+/// version for a text or a symbol and print where it was found.
+pub fn search(
+    config: &Config,
+    query: &str,
+    symbol: bool,
+    format: SearchFormat,
+    limit: usize,
+) -> MojResult<()> {
+    let repo = open_repository(config)?;
+    let mut versions = repo.list_versions()?;
+    // The placeholder of a failed version would split the stretches of versions a match is in
+    versions.retain(|version| !version.is_failed());
+    if versions.is_empty() {
+        return Err(Report::new(MojError::UserError)
+            .attach("The repository has no versions yet")
+            .attach("Run an update first"));
+    }
+    let index = Path::new(INDEX_PATH);
+    update_index(&repo, index, &versions)?;
+    let segments = versions
+        .iter()
+        .map(|version| read_segment(&segment_path(index, &version.id)))
+        .collect::<MojResult<Vec<_>>>()?;
+
+    let rendered = if symbol {
+        let histories = search_symbols(&versions, &segments, query);
+        log::info!(
+            "Found {} matching symbols",
+            histories.len().as_important_value()
+        );
+        match format {
+            SearchFormat::Text => render_symbols(&histories, limit),
+            SearchFormat::Json => to_json(&histories)?,
+        }
+    } else {
+        let files = search_text(&repo, &versions, &segments, query)?;
+        log::info!("Found in {} files", files.len().as_important_value());
+        if let (Some(first), Some(last)) = (
+            files.first().map(|f| &f.matches[0].first_version),
+            files
+                .iter()
+                .flat_map(|f| &f.matches)
+                .map(|m| &m.last_version)
+                .max_by_key(|id| versions.iter().position(|v| &v.id == *id)),
+        ) {
+            log::info!(
+                "First seen in {}, last seen in {}",
+                first.as_important_value(),
+                last.as_important_value()
+            );
+        }
+        match format {
+            SearchFormat::Text => render_text_matches(&files, limit),
+            SearchFormat::Json => to_json(&files)?,
+        }
+    };
+    print!("{}", rendered);
+    Ok(())
+}
@@ -0,0 +1,287 @@
+use crate::class_artifacts::{api_signatures_path, parse_api_signatures};
+use crate::colorize::InfoColors;
+use crate::html::decode_url;
+use crate::repository::{MojRepository, VersionCommit};
+use crate::{error_message, open_repository, Config, MojError, MojResult, SavedInfo};
+use error_stack::{Report, ResultExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// How many symbols a search returns unless the request asks for a different limit.
+const DEFAULT_SYMBOL_LIMIT: usize = 100;
+/// How long to wait for a client to send its request before giving up on it.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(value: &impl Serialize) -> Self {
+        match serde_json::to_vec_pretty(value) {
+            Ok(body) => Response {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(e) => Response::error(500, &format!("Failed to serialize response: {}", e)),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        #[derive(Serialize)]
+        struct Error<'a> {
+            error: &'a str,
+        }
+        Response {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(&Error { error: message }).unwrap(),
+        }
+    }
+
+    /// Respond with the failure of a request. Errors caused by the request itself, such as asking
+    /// for a version that isn't in the repository, are reported as not found.
+    fn from_report(report: &Report<MojError>) -> Self {
+        let status = match report.current_context() {
+            MojError::UserError => 404,
+            _ => 500,
+        };
+        Response::error(status, &error_message(report))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct VersionJson<'a> {
+    id: &'a str,
+    release_time: i64,
+    commit: String,
+    tree: String,
+    /// Whether the commit is only a placeholder for a version that failed to generate.
+    failed: bool,
+    info: &'a SavedInfo,
+}
+
+impl<'a> From<&'a VersionCommit> for VersionJson<'a> {
+    fn from(version: &'a VersionCommit) -> Self {
+        VersionJson {
+            id: &version.id,
+            release_time: version.release_time,
+            commit: version.commit.to_string(),
+            tree: version.tree.to_string(),
+            failed: version.is_failed(),
+            info: &version.info,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SymbolJson<'a> {
+    symbol: &'a str,
+    declaration: &'a str,
+}
+
+#[derive(Serialize)]
+struct SymbolSearchJson<'a> {
+    version: &'a str,
+    symbols: Vec<SymbolJson<'a>>,
+    /// Whether more symbols matched than were returned.
+    truncated: bool,
+}
+
+/// Serve a JSON API over the versions in the repository until the process is killed. Requests are
+/// handled one at a time, which is plenty for local tools.
+pub fn serve(config: &Config, address: &str) -> MojResult<()> {
+    let repo = open_repository(config)?;
+    let listener = TcpListener::bind(address)
+        .change_context(MojError::Serve)
+        .attach_with(|| format!("Address: {}", address))?;
+    let local_address = listener
+        .local_addr()
+        .map_or_else(|_| address.to_string(), |a| a.to_string());
+    log::info!(
+        "Serving the repository on {}",
+        format!("http://{}/", local_address).as_important_value()
+    );
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| handle_connection(&repo, stream));
+        if let Err(e) = result {
+            log::warn!("Failed to handle connection: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection(repo: &MojRepository, stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers don't matter, but have to be read before responding
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let response = match method {
+        "GET" | "HEAD" => {
+            let (path, query) = target.split_once('?').unwrap_or((target, ""));
+            route(repo, path, query).unwrap_or_else(|report| Response::from_report(&report))
+        }
+        _ => Response::error(405, "Only GET requests are supported"),
+    };
+    log::info!("{} {} {}", method, target, response.status);
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(&response.body)?;
+    }
+    stream.flush()
+}
+
+fn parse_query(query: &str) -> Option<HashMap<String, String>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((
+                decode_url(&key.replace('+', " "))?,
+                decode_url(&value.replace('+', " "))?,
+            ))
+        })
+        .collect()
+}
+
+fn route(repo: &MojRepository, path: &str, query: &str) -> MojResult<Response> {
+    let Some(segments) = path
+        .trim_matches('/')
+        .split('/')
+        .map(decode_url)
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Ok(Response::error(400, "Invalid path encoding"));
+    };
+    let Some(query) = parse_query(query) else {
+        return Ok(Response::error(400, "Invalid query encoding"));
+    };
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    match segments.as_slice() {
+        ["versions"] => {
+            let versions = repo.list_versions()?;
+            let versions: Vec<VersionJson> = versions.iter().map(VersionJson::from).collect();
+            Ok(Response::json(&versions))
+        }
+        ["versions", id] => {
+            let versions = repo.list_versions()?;
+            match versions.iter().find(|version| version.id == *id) {
+                Some(version) => Ok(Response::json(&VersionJson::from(version))),
+                None => Ok(Response::error(
+                    404,
+                    &format!("Version {} is not in the repository", id),
+                )),
+            }
+        }
+        ["versions", id, "files", file @ ..] if !file.is_empty() => {
+            let file = file.join("/");
+            match repo.read_version_file(id, &file)? {
+                Some(contents) => Ok(Response {
+                    status: 200,
+                    content_type: if std::str::from_utf8(&contents).is_ok() {
+                        "text/plain; charset=utf-8"
+                    } else {
+                        "application/octet-stream"
+                    },
+                    body: contents,
+                }),
+                None => Ok(Response::error(
+                    404,
+                    &format!("Version {} has no file {}", id, file),
+                )),
+            }
+        }
+        ["diff"] => {
+            let (Some(from), Some(to)) = (query.get("from"), query.get("to")) else {
+                return Ok(Response::error(400, "Both from and to must be given"));
+            };
+            let mut diffs = Vec::new();
+            repo.for_each_file_diff(
+                repo.version_tree(from)?,
+                repo.version_tree(to)?,
+                query.get("path").map(String::as_str),
+                |diff| {
+                    diffs.push(diff);
+                    Ok(())
+                },
+            )?;
+            Ok(Response::json(&diffs))
+        }
+        ["symbols"] => {
+            let Some(pattern) = query.get("q") else {
+                return Ok(Response::error(400, "A query must be given as q"));
+            };
+            let limit = match query.get("limit").map(|limit| limit.parse()) {
+                None => DEFAULT_SYMBOL_LIMIT,
+                Some(Ok(limit)) => limit,
+                Some(Err(_)) => return Ok(Response::error(400, "Invalid limit")),
+            };
+            let version = match query.get("version") {
+                Some(version) => version.clone(),
+                None => match repo
+                    .list_versions()?
+                    .into_iter()
+                    .rfind(|version| !version.is_failed())
+                {
+                    Some(latest) => latest.id,
+                    None => return Ok(Response::error(404, "The repository has no versions")),
+                },
+            };
+            let contents = repo
+                .read_version_file(&version, &api_signatures_path())?
+                .unwrap_or_default();
+            let contents = String::from_utf8_lossy(&contents);
+            let pattern = pattern.to_lowercase();
+            let mut matches = parse_api_signatures(&contents)
+                .filter(|(symbol, _)| symbol.to_lowercase().contains(&pattern))
+                .map(|(symbol, declaration)| SymbolJson {
+                    symbol,
+                    declaration,
+                });
+            let symbols: Vec<_> = matches.by_ref().take(limit).collect();
+            Ok(Response::json(&SymbolSearchJson {
+                version: &version,
+                symbols,
+                truncated: matches.next().is_some(),
+            }))
+        }
+        _ => Ok(Response::error(404, "No such endpoint")),
+    }
+}
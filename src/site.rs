@@ -0,0 +1,646 @@
+use crate::colorize::InfoColors;
+use crate::highlight::{highlight, is_java};
+use crate::html::{encode_url, escape_html};
+use crate::logging;
+use crate::repository::{
+    DiffLine, DiffLineKind, FileChangeKind, FileDiff, MojRepository, VersionCommit,
+};
+use crate::{open_repository, version_file_name, Config, MojError, MojResult};
+use chrono::DateTime;
+use error_stack::{Report, ResultExt};
+use git2::Oid;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+const STYLESHEET: &str = "\
+body { font-family: sans-serif; margin: 1em 2em; }
+nav { margin-bottom: 1em; }
+table { border-collapse: collapse; }
+td, th { padding: 0 0.5em; text-align: left; vertical-align: top; }
+td.num { text-align: right; }
+ul.tree { list-style: none; padding-left: 1.2em; }
+summary { cursor: pointer; }
+.added { color: #1a7f37; }
+.removed { color: #cf222e; }
+table.code { font-family: monospace; font-size: 0.9em; width: 100%; }
+table.code td { white-space: pre-wrap; word-break: break-all; }
+table.code td.ln { color: #888; text-align: right; user-select: none; width: 1%; white-space: nowrap; }
+table.code td.line-added { background: #e6ffec; }
+table.code td.line-removed { background: #ffebe9; }
+table.code td.line-empty { background: #f6f8fa; }
+table.code tr.hunk td { background: #ddf4ff; color: #57606a; }
+.k { color: #cf222e; }
+.s { color: #0a3069; }
+.c { color: #6e7781; font-style: italic; }
+.n { color: #0550ae; }
+.a { color: #8250df; }
+";
+
+/// What each page was rendered from, so pages whose inputs didn't change aren't rendered again.
+#[derive(Serialize, Deserialize, Default)]
+struct Stamps {
+    /// The layout of the site the pages were rendered with. Stamps of another layout are
+    /// discarded, so every page is rendered again.
+    #[serde(default)]
+    layout: u32,
+    /// The inputs of each version page, by the page path.
+    versions: HashMap<String, String>,
+    /// The old and new tree of each diff and how many files it changed, by the diff directory.
+    diffs: HashMap<String, DiffStamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct DiffStamp {
+    old_tree: String,
+    new_tree: String,
+    changed_files: usize,
+}
+
+impl Stamps {
+    const FILE_NAME: &'static str = "stamps.json";
+    /// Bumped when the paths or contents of the pages change.
+    const LAYOUT: u32 = 1;
+
+    fn load(root: &Path) -> Self {
+        std::fs::read(root.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Self>(&json).ok())
+            .filter(|stamps| stamps.layout == Self::LAYOUT)
+            .unwrap_or_default()
+    }
+
+    fn save(&self, root: &Path) -> MojResult<()> {
+        let json = serde_json::to_string_pretty(self)
+            .change_context(MojError::Site)
+            .attach("Failed to serialize page stamps")?;
+        write_file(&root.join(Self::FILE_NAME), &json)
+    }
+}
+
+fn version_page(version_id: &str) -> String {
+    format!("versions/{}.html", version_file_name(version_id))
+}
+
+fn diff_dir(from: &str, to: &str) -> String {
+    format!(
+        "diffs/{}..{}",
+        version_file_name(from),
+        version_file_name(to)
+    )
+}
+
+/// Blob pages are shared by every file with the blob as its contents and the same extension, which
+/// decides how it is highlighted, and never change.
+fn blob_page(oid: Oid, path: &str) -> String {
+    let hex = oid.to_string();
+    let extension = path
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension)
+        .filter(|extension| {
+            !extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    match extension {
+        Some(extension) => format!("blobs/{}/{}.{}.html", &hex[..2], hex, extension),
+        None => format!("blobs/{}/{}.html", &hex[..2], hex),
+    }
+}
+
+fn format_date(time: i64) -> String {
+    DateTime::from_timestamp(time, 0)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn write_file(path: &Path, contents: &str) -> MojResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .change_context(MojError::Site)
+            .attach_with(|| format!("Path: {:?}", parent))?;
+    }
+    std::fs::write(path, contents)
+        .change_context(MojError::Site)
+        .attach_with(|| format!("Path: {:?}", path))
+}
+
+/// Wrap the body of a page. `root` is the relative path from the page to the root of the site.
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <link rel=\"stylesheet\" href=\"{root}style.css\">\n</head>\n<body>\n\
+         <nav><a href=\"{root}index.html\">All versions</a></nav>\n{body}</body>\n</html>\n",
+        title = escape_html(title),
+    )
+}
+
+/// The contents of a file as text, if it isn't binary.
+fn as_text(contents: &[u8]) -> Option<&str> {
+    std::str::from_utf8(contents)
+        .ok()
+        .filter(|_| !contents.contains(&0))
+}
+
+/// Render a static HTML site of the repository: a timeline of the versions, the files of each
+/// version, and the diffs between adjacent versions.
+pub fn site(config: &Config, output: Option<PathBuf>) -> MojResult<()> {
+    let repo = open_repository(config)?;
+    let all_versions = repo.list_versions()?;
+    // Placeholders of failed versions have nothing to show, so they are only listed in the timeline
+    let versions: Vec<_> = all_versions
+        .iter()
+        .filter(|version| !version.is_failed())
+        .cloned()
+        .collect();
+    if versions.is_empty() {
+        return Err(Report::new(MojError::UserError)
+            .attach("The repository has no versions yet")
+            .attach("Run an update first"));
+    }
+    let root = output.unwrap_or_else(|| PathBuf::from("./site"));
+    log::info!(
+        "Rendering site of {} versions to {}...",
+        versions.len().as_important_value(),
+        root.display().as_important_value()
+    );
+    let old_stamps = Stamps::load(&root);
+    let mut stamps = Stamps {
+        layout: Stamps::LAYOUT,
+        ..Stamps::default()
+    };
+    let mut blob_pages = HashSet::new();
+    write_file(&root.join("style.css"), STYLESHEET)?;
+
+    let progress_bar = logging::progress_bar(
+        versions.len() as u64,
+        "Site progress: {bar:40.white/blue} {pos:.cyan}/{len:.cyan} ({msg})",
+    );
+    let mut pages_written = 0;
+    for (i, version) in versions.iter().enumerate() {
+        progress_bar.set_message(version.id.clone());
+        let previous = i.checked_sub(1).map(|i| &versions[i]);
+        let next = versions.get(i + 1);
+
+        if let Some(previous) = previous {
+            let dir = diff_dir(&previous.id, &version.id);
+            let old_stamp = old_stamps.diffs.get(&dir).filter(|stamp| {
+                stamp.old_tree == previous.tree.to_string()
+                    && stamp.new_tree == version.tree.to_string()
+            });
+            let stamp = match old_stamp {
+                Some(stamp) => stamp.clone(),
+                None => {
+                    let changed_files = write_diff(&repo, &root, &dir, previous, version)?;
+                    pages_written += changed_files + 1;
+                    DiffStamp {
+                        old_tree: previous.tree.to_string(),
+                        new_tree: version.tree.to_string(),
+                        changed_files,
+                    }
+                }
+            };
+            stamps.diffs.insert(dir, stamp);
+        }
+
+        let files = repo.tree_files(version.tree)?;
+        blob_pages.extend(
+            files
+                .iter()
+                .map(|(file, oid)| root.join(blob_page(*oid, file))),
+        );
+        let path = version_page(&version.id);
+        let stamp = format!(
+            "{}\n{:?}\n{:?}\n{}",
+            version.tree,
+            previous.map(|v| &v.id),
+            next.map(|v| &v.id),
+            version.message
+        );
+        if old_stamps.versions.get(&path) != Some(&stamp) {
+            pages_written += write_version(&repo, &root, &path, version, &files, previous, next)?;
+        }
+        stamps.versions.insert(path, stamp);
+        progress_bar.inc(1);
+    }
+    progress_bar.finish_and_clear();
+
+    write_file(
+        &root.join("index.html"),
+        &render_timeline(&all_versions, &stamps),
+    )?;
+    remove_stale_pages(&root, &stamps, &blob_pages)?;
+    stamps.save(&root)?;
+    log::info!(
+        "Wrote {} pages to {}",
+        pages_written.as_important_value(),
+        root.display().as_important_value()
+    );
+    Ok(())
+}
+
+/// Render the list of every version, including those that failed to generate, newest first.
+fn render_timeline(versions: &[VersionCommit], stamps: &Stamps) -> String {
+    let mut body = String::new();
+    writeln!(body, "<h1>Versions</h1>\n<table>").unwrap();
+    writeln!(
+        body,
+        "<tr><th>Version</th><th>Released</th><th>Changes</th></tr>"
+    )
+    .unwrap();
+    for (i, version) in versions.iter().enumerate().rev() {
+        if version.is_failed() {
+            writeln!(
+                body,
+                "<tr><td>{}</td><td>{}</td><td>Failed to generate</td></tr>",
+                escape_html(&version.id),
+                format_date(version.release_time)
+            )
+            .unwrap();
+            continue;
+        }
+        let previous = versions[..i].iter().rev().find(|v| !v.is_failed());
+        let changes = match previous {
+            Some(previous) => {
+                let dir = diff_dir(&previous.id, &version.id);
+                let changed_files = stamps.diffs.get(&dir).map_or(0, |s| s.changed_files);
+                format!(
+                    "<a href=\"{}/index.html\">{} files changed since {}</a>",
+                    encode_url(&dir),
+                    changed_files,
+                    escape_html(&previous.id)
+                )
+            }
+            None => String::new(),
+        };
+        writeln!(
+            body,
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+            encode_url(&version_page(&version.id)),
+            escape_html(&version.id),
+            format_date(version.release_time),
+            changes
+        )
+        .unwrap();
+    }
+    writeln!(body, "</table>").unwrap();
+    page("Versions", "", &body)
+}
+
+/// A directory of a version, for rendering its file tree.
+#[derive(Default)]
+struct Dir<'a> {
+    dirs: BTreeMap<&'a str, Dir<'a>>,
+    files: BTreeMap<&'a str, Oid>,
+}
+
+impl<'a> Dir<'a> {
+    fn insert(&mut self, path: &'a str, oid: Oid) {
+        match path.split_once('/') {
+            Some((dir, rest)) => self.dirs.entry(dir).or_default().insert(rest, oid),
+            None => {
+                self.files.insert(path, oid);
+            }
+        }
+    }
+
+    fn render(&self, out: &mut String) {
+        writeln!(out, "<ul class=\"tree\">").unwrap();
+        for (name, dir) in &self.dirs {
+            // Show chains of directories with nothing else in them, like packages, as one entry
+            let mut name = name.to_string();
+            let mut dir = dir;
+            while dir.files.is_empty() && dir.dirs.len() == 1 {
+                let (child_name, child) = dir.dirs.iter().next().unwrap();
+                name = format!("{}/{}", name, child_name);
+                dir = child;
+            }
+            writeln!(
+                out,
+                "<li><details><summary>{}/</summary>",
+                escape_html(&name)
+            )
+            .unwrap();
+            dir.render(out);
+            writeln!(out, "</details></li>").unwrap();
+        }
+        for (name, oid) in &self.files {
+            writeln!(
+                out,
+                "<li><a href=\"../{}\">{}</a></li>",
+                blob_page(*oid, name),
+                escape_html(name)
+            )
+            .unwrap();
+        }
+        writeln!(out, "</ul>").unwrap();
+    }
+}
+
+/// Write the page of a version and the pages of its files that don't exist yet. Returns how many
+/// pages were written.
+fn write_version(
+    repo: &MojRepository,
+    root: &Path,
+    path: &str,
+    version: &VersionCommit,
+    files: &[(String, Oid)],
+    previous: Option<&VersionCommit>,
+    next: Option<&VersionCommit>,
+) -> MojResult<usize> {
+    let mut pages_written = 1;
+    for (file, oid) in files {
+        let blob_path = root.join(blob_page(*oid, file));
+        if !blob_path.exists() {
+            write_file(&blob_path, &render_blob(*oid, file, &repo.read_blob(*oid)?))?;
+            pages_written += 1;
+        }
+    }
+
+    let mut body = String::new();
+    writeln!(body, "<h1>Version {}</h1>", escape_html(&version.id)).unwrap();
+    writeln!(
+        body,
+        "<p>Released {}. {} files.</p>",
+        format_date(version.release_time),
+        files.len()
+    )
+    .unwrap();
+    let mut links = Vec::new();
+    if let Some(previous) = previous {
+        links.push(format!(
+            "<a href=\"../{}/index.html\">Changes since {}</a>",
+            encode_url(&diff_dir(&previous.id, &version.id)),
+            escape_html(&previous.id)
+        ));
+    }
+    if let Some(next) = next {
+        links.push(format!(
+            "<a href=\"../{}/index.html\">Changes in {}</a>",
+            encode_url(&diff_dir(&version.id, &next.id)),
+            escape_html(&next.id)
+        ));
+    }
+    if !links.is_empty() {
+        writeln!(body, "<p>{}</p>", links.join(" | ")).unwrap();
+    }
+    // The summary line only repeats the version id
+    let details = version
+        .message
+        .split_once('\n')
+        .map_or("", |(_, rest)| rest.trim());
+    if !details.is_empty() {
+        writeln!(body, "<pre>{}</pre>", escape_html(details)).unwrap();
+    }
+    let mut tree = Dir::default();
+    for (file, oid) in files {
+        tree.insert(file, *oid);
+    }
+    tree.render(&mut body);
+    write_file(
+        &root.join(path),
+        &page(&format!("Version {}", version.id), "../", &body),
+    )?;
+    Ok(pages_written)
+}
+
+/// Render the page of a blob. The page is shared by files of other names, so it is titled after the
+/// blob, and the version pages name the files.
+fn render_blob(oid: Oid, path: &str, contents: &[u8]) -> String {
+    let title = format!("Blob {}", &oid.to_string()[..12]);
+    let mut body = String::new();
+    writeln!(body, "<h1>{}</h1>", escape_html(&title)).unwrap();
+    match as_text(contents) {
+        Some(text) => {
+            writeln!(body, "<table class=\"code\">").unwrap();
+            for (i, line) in highlight(path, text).iter().enumerate() {
+                writeln!(
+                    body,
+                    "<tr id=\"L{n}\"><td class=\"ln\"><a href=\"#L{n}\">{n}</a></td><td>{}</td></tr>",
+                    line,
+                    n = i + 1
+                )
+                .unwrap();
+            }
+            writeln!(body, "</table>").unwrap();
+        }
+        None => writeln!(body, "<p>Binary file, {} bytes.</p>", contents.len()).unwrap(),
+    }
+    page(&title, "../../", &body)
+}
+
+/// Write the pages of the diff between two versions: one listing the changed files, and one
+/// side-by-side diff per file. Returns how many files changed.
+fn write_diff(
+    repo: &MojRepository,
+    root: &Path,
+    dir: &str,
+    old: &VersionCommit,
+    new: &VersionCommit,
+) -> MojResult<usize> {
+    let path = root.join(dir);
+    // Files are numbered, so pages of a previous render may no longer match
+    if path.exists() {
+        std::fs::remove_dir_all(&path)
+            .change_context(MojError::Site)
+            .attach_with(|| format!("Path: {:?}", path))?;
+    }
+    let mut rows = String::new();
+    let mut changed_files = 0;
+    repo.for_each_file_diff(old.tree, new.tree, None, |diff| {
+        changed_files += 1;
+        let change = &diff.change;
+        let name = match change.kind {
+            FileChangeKind::Renamed => format!(
+                "{} &rarr; {}",
+                escape_html(change.old_path.as_deref().unwrap_or_default()),
+                escape_html(change.path())
+            ),
+            _ => escape_html(change.path()),
+        };
+        writeln!(
+            rows,
+            "<tr><td>{:?}</td><td><a href=\"{}.html\">{}</a></td>\
+             <td class=\"num added\">+{}</td><td class=\"num removed\">-{}</td></tr>",
+            change.kind, changed_files, name, change.insertions, change.deletions
+        )
+        .unwrap();
+        let title = format!("{} ({} to {})", change.path(), old.id, new.id);
+        let mut body = format!("<h1>{}</h1>\n", name);
+        body.push_str(&render_side_by_side(repo, old, new, &diff)?);
+        write_file(
+            &path.join(format!("{}.html", changed_files)),
+            &page(&title, "../../", &body),
+        )
+    })?;
+
+    let title = format!("Changes from {} to {}", old.id, new.id);
+    let mut body = String::new();
+    writeln!(body, "<h1>{}</h1>", escape_html(&title)).unwrap();
+    writeln!(
+        body,
+        "<p><a href=\"../../{}\">{}</a> | <a href=\"../../{}\">{}</a></p>",
+        encode_url(&version_page(&old.id)),
+        escape_html(&old.id),
+        encode_url(&version_page(&new.id)),
+        escape_html(&new.id)
+    )
+    .unwrap();
+    if changed_files == 0 {
+        writeln!(body, "<p>No files changed.</p>").unwrap();
+    } else {
+        writeln!(body, "<table>\n{}</table>", rows).unwrap();
+    }
+    write_file(&path.join("index.html"), &page(&title, "../../", &body))?;
+    Ok(changed_files)
+}
+
+/// The highlighted lines of a file in a tree, if it is a Java source.
+fn highlighted_lines(
+    repo: &MojRepository,
+    tree: Oid,
+    path: Option<&str>,
+) -> MojResult<Option<Vec<String>>> {
+    let Some(path) = path.filter(|path| is_java(path)) else {
+        return Ok(None);
+    };
+    let contents = repo.read_tree_file(tree, path)?.unwrap_or_default();
+    Ok(as_text(&contents).map(|text| highlight(path, text)))
+}
+
+fn render_side_by_side(
+    repo: &MojRepository,
+    old: &VersionCommit,
+    new: &VersionCommit,
+    diff: &FileDiff,
+) -> MojResult<String> {
+    if diff.binary {
+        return Ok("<p>Binary file changed.</p>\n".to_string());
+    }
+    if diff.hunks.is_empty() {
+        return Ok("<p>Only the path changed.</p>\n".to_string());
+    }
+    // Highlight whole files rather than the lines of the diff, as comments can span lines
+    let old_lines = highlighted_lines(repo, old.tree, diff.change.old_path.as_deref())?;
+    let new_lines = highlighted_lines(repo, new.tree, diff.change.new_path.as_deref())?;
+    let cell = |line: Option<&DiffLine>, number: Option<u32>, lines: &Option<Vec<String>>| {
+        let Some(line) = line else {
+            return "<td class=\"ln\"></td><td class=\"line-empty\"></td>".to_string();
+        };
+        let class = match line.kind {
+            DiffLineKind::Context => "",
+            DiffLineKind::Added => " class=\"line-added\"",
+            DiffLineKind::Removed => " class=\"line-removed\"",
+        };
+        let html = number
+            .and_then(|n| lines.as_ref()?.get(n as usize - 1).cloned())
+            .unwrap_or_else(|| escape_html(&line.content));
+        format!(
+            "<td class=\"ln\">{}</td><td{}>{}</td>",
+            number.map(|n| n.to_string()).unwrap_or_default(),
+            class,
+            html
+        )
+    };
+
+    let mut out = String::new();
+    writeln!(out, "<table class=\"code\">").unwrap();
+    for hunk in &diff.hunks {
+        writeln!(
+            out,
+            "<tr class=\"hunk\"><td colspan=\"4\">&#8943;</td></tr>"
+        )
+        .unwrap();
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        let flush = |out: &mut String, removed: &mut Vec<&DiffLine>, added: &mut Vec<&DiffLine>| {
+            // Pair up removed and added lines, so a changed line shows next to what replaced it
+            for row in 0..removed.len().max(added.len()) {
+                let old_line = removed.get(row).copied();
+                let new_line = added.get(row).copied();
+                writeln!(
+                    out,
+                    "<tr>{}{}</tr>",
+                    cell(old_line, old_line.and_then(|l| l.old_line), &old_lines),
+                    cell(new_line, new_line.and_then(|l| l.new_line), &new_lines)
+                )
+                .unwrap();
+            }
+            removed.clear();
+            added.clear();
+        };
+        for line in hunk {
+            match line.kind {
+                DiffLineKind::Removed => removed.push(line),
+                DiffLineKind::Added => added.push(line),
+                DiffLineKind::Context => {
+                    flush(&mut out, &mut removed, &mut added);
+                    writeln!(
+                        out,
+                        "<tr>{}{}</tr>",
+                        cell(Some(line), line.old_line, &old_lines),
+                        cell(Some(line), line.new_line, &new_lines)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        flush(&mut out, &mut removed, &mut added);
+    }
+    writeln!(out, "</table>").unwrap();
+    Ok(out)
+}
+
+/// Remove the pages of versions and diffs that are no longer in the repository, and the pages of
+/// blobs no version contains anymore.
+fn remove_stale_pages(
+    root: &Path,
+    stamps: &Stamps,
+    blob_pages: &HashSet<PathBuf>,
+) -> MojResult<()> {
+    let current: HashSet<PathBuf> = stamps
+        .versions
+        .keys()
+        .chain(stamps.diffs.keys())
+        .map(|path| root.join(path))
+        .collect();
+    for dir in ["versions", "diffs"] {
+        let Ok(entries) = std::fs::read_dir(root.join(dir)) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry.change_context(MojError::Site)?.path();
+            if current.contains(&path) {
+                continue;
+            }
+            if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            }
+            .change_context(MojError::Site)
+            .attach_with(|| format!("Path: {:?}", path))?;
+        }
+    }
+    let Ok(blob_dirs) = std::fs::read_dir(root.join("blobs")) else {
+        return Ok(());
+    };
+    for blob_dir in blob_dirs {
+        let blob_dir = blob_dir.change_context(MojError::Site)?.path();
+        let entries = std::fs::read_dir(&blob_dir)
+            .change_context(MojError::Site)
+            .attach_with(|| format!("Path: {:?}", blob_dir))?;
+        for entry in entries {
+            let path = entry.change_context(MojError::Site)?.path();
+            if !blob_pages.contains(&path) {
+                std::fs::remove_file(&path)
+                    .change_context(MojError::Site)
+                    .attach_with(|| format!("Path: {:?}", path))?;
+            }
+        }
+        // Only succeeds once every page in it is gone
+        let _ = std::fs::remove_dir(&blob_dir);
+    }
+    Ok(())
+}